@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+
+use crate::auth::md5;
+
+// How a user's password is stored in the user file.
+#[derive(Debug)]
+enum Password {
+    // An htpasswd entry made with `htpasswd -s`: "{SHA}" followed by the base64 SHA-1 of the
+    // password. These users can only log in with Basic authentication.
+    Sha1(Vec<u8>),
+    // An htdigest entry: the hex MD5 of "user:realm:password", which is all Digest authentication
+    // needs to verify a response.
+    // https://tools.ietf.org/html/rfc7616#section-3.4.2
+    Digest(String),
+}
+
+// The users allowed to authenticate, read from a file in the formats written by Apache's htpasswd
+// (with -s) and htdigest. Both kinds of entry may be mixed in the same file.
+#[derive(Debug, Default)]
+pub struct Users {
+    passwords: HashMap<String, Password>,
+}
+
+impl Users {
+    pub fn load(path: &Path, realm: &str) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut users = Users::default();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (user, password) = parse_entry(line, realm)
+                .map_err(|e| format!("{}:{}: {}", path.display(), i + 1, e))?;
+            users.passwords.insert(user, password);
+        }
+        Ok(users)
+    }
+
+    // Checks a password given with Basic authentication.
+    pub fn verify_password(&self, user: &str, password: &str, realm: &str) -> bool {
+        match self.passwords.get(user) {
+            Some(Password::Sha1(hash)) => constant_time_eq(
+                digest(&SHA1_FOR_LEGACY_USE_ONLY, password.as_bytes()).as_ref(),
+                hash,
+            ),
+            Some(Password::Digest(ha1)) => constant_time_eq(
+                md5::hex_digest(format!("{}:{}:{}", user, realm, password).as_bytes()).as_bytes(),
+                ha1.as_bytes(),
+            ),
+            None => false,
+        }
+    }
+
+    // Returns the H(A1) value Digest authentication needs, if the user has an htdigest entry.
+    pub fn digest_secret(&self, user: &str) -> Option<&str> {
+        match self.passwords.get(user) {
+            Some(Password::Digest(ha1)) => Some(ha1),
+            _ => None,
+        }
+    }
+}
+
+// "user:{SHA}hash" or "user:realm:hash"
+fn parse_entry(line: &str, realm: &str) -> Result<(String, Password), String> {
+    let fields: Vec<&str> = line.split(':').collect();
+    match fields.as_slice() {
+        [user, hash] if hash.starts_with("{SHA}") => {
+            let hash = STANDARD
+                .decode(&hash["{SHA}".len()..])
+                .map_err(|_| format!("invalid SHA-1 password for {}", user))?;
+            Ok((String::from(*user), Password::Sha1(hash)))
+        }
+        [user, _] => Err(format!(
+            "unsupported password format for {}; use htpasswd -s or htdigest",
+            user
+        )),
+        [user, entry_realm, hash] => {
+            if *entry_realm != realm {
+                return Err(format!(
+                    "{} belongs to realm \"{}\" rather than \"{}\"",
+                    user, entry_realm, realm
+                ));
+            }
+            if hash.len() != 32 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("invalid digest for {}", user));
+            }
+            Ok((String::from(*user), Password::Digest(hash.to_lowercase())))
+        }
+        _ => Err(String::from("expected user:password or user:realm:digest")),
+    }
+}
+
+// Compares secrets without revealing how much of them matched.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,13 @@
+// MD5 is only used because HTTP Digest authentication and htdigest files are defined in terms of
+// it; it offers no collision resistance and must not be used for anything else.
+// https://tools.ietf.org/html/rfc1321
+
+use ::md5::{Digest, Md5};
+
+// Digest authentication exchanges hashes as lowercase hex strings.
+pub fn hex_digest(data: &[u8]) -> String {
+    Md5::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hyper::{header, Body, Request};
+use ring::hmac;
+use ring::rand::SystemRandom;
+
+pub use crate::auth::htpasswd::Users;
+use crate::printer::UriAuthenticationMethod;
+
+mod htpasswd;
+mod md5;
+
+// The protection space clients are asked to log in to. htdigest entries must be made for it.
+// https://tools.ietf.org/html/rfc7235#section-2.2
+pub const REALM: &str = "print-srv";
+// How long a Digest nonce may be used before the client has to ask for a new one.
+const NONCE_LIFETIME: Duration = Duration::from_secs(5 * 60);
+
+// Checks the credentials sent with HTTP requests against the users file. Nonces are not stored
+// when they are handed out; each one carries its creation time and is signed with a key which only
+// lives as long as the process, so a nonce can be checked without remembering which ones were made.
+// Once a nonce has been used, the highest nonce-count sent with it is kept until it expires, so that
+// a response captured on the network cannot be replayed.
+// https://tools.ietf.org/html/rfc7616#section-5.5
+pub struct Authenticator {
+    users: Users,
+    nonce_key: hmac::Key,
+    nonce_counts: Mutex<HashMap<String, NonceCount>>,
+}
+
+// The highest nonce-count a nonce has been used with, and when the nonce expires.
+struct NonceCount {
+    expires: Instant,
+    count: u32,
+}
+
+impl fmt::Debug for Authenticator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Authenticator").finish_non_exhaustive()
+    }
+}
+
+impl Authenticator {
+    pub fn new(users: Users) -> Result<Self, String> {
+        let nonce_key = hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new())
+            .map_err(|_| String::from("Could not generate a nonce key"))?;
+        Ok(Self {
+            users,
+            nonce_key,
+            nonce_counts: Mutex::default(),
+        })
+    }
+
+    // Returns the name of the user a request was made by, or the WWW-Authenticate challenge to
+    // send back if the request does not carry valid credentials for the given scheme.
+    // https://tools.ietf.org/html/rfc7235#section-4.1
+    pub fn authenticate(
+        &self,
+        scheme: UriAuthenticationMethod,
+        req: &Request<Body>,
+    ) -> Result<String, String> {
+        let authorization = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let (auth_scheme, credentials) = match authorization.find(' ') {
+            Some(i) => (&authorization[..i], authorization[i + 1..].trim()),
+            None => (authorization, ""),
+        };
+
+        match scheme {
+            UriAuthenticationMethod::Basic if auth_scheme.eq_ignore_ascii_case("Basic") => self
+                .authenticate_basic(credentials)
+                .ok_or_else(|| self.challenge(scheme, false)),
+            UriAuthenticationMethod::Digest if auth_scheme.eq_ignore_ascii_case("Digest") => {
+                self.authenticate_digest(credentials, req)
+            }
+            _ => Err(self.challenge(scheme, false)),
+        }
+    }
+
+    // https://tools.ietf.org/html/rfc7617#section-2
+    fn authenticate_basic(&self, credentials: &str) -> Option<String> {
+        let decoded = STANDARD.decode(credentials).ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (user, password) = decoded.split_at(decoded.find(':')?);
+        if self.users.verify_password(user, &password[1..], REALM) {
+            Some(String::from(user))
+        } else {
+            None
+        }
+    }
+
+    // https://tools.ietf.org/html/rfc7616#section-3.4
+    fn authenticate_digest(
+        &self,
+        credentials: &str,
+        req: &Request<Body>,
+    ) -> Result<String, String> {
+        let challenge = || self.challenge(UriAuthenticationMethod::Digest, false);
+        let params = parse_params(credentials);
+        let param = |name: &str| params.get(name).map(String::as_str);
+
+        let (user, nonce, uri, response) = match (
+            param("username"),
+            param("nonce"),
+            param("uri"),
+            param("response"),
+        ) {
+            (Some(user), Some(nonce), Some(uri), Some(response)) => (user, nonce, uri, response),
+            _ => return Err(challenge()),
+        };
+        if param("realm") != Some(REALM)
+            || !matches!(param("algorithm"), None | Some("MD5"))
+            || req.uri() != uri
+        {
+            return Err(challenge());
+        }
+        let ha1 = self.users.digest_secret(user).ok_or_else(challenge)?;
+        let ha2 = md5::hex_digest(format!("{}:{}", req.method(), uri).as_bytes());
+        let expected = match (param("qop"), param("nc"), param("cnonce")) {
+            (Some("auth"), Some(nc), Some(cnonce)) => md5::hex_digest(
+                format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2).as_bytes(),
+            ),
+            // Clients which predate qop hash only the nonce.
+            // https://tools.ietf.org/html/rfc2069#section-2.1.2
+            (None, None, None) => md5::hex_digest(format!("{}:{}:{}", ha1, nonce, ha2).as_bytes()),
+            _ => return Err(challenge()),
+        };
+        if !htpasswd::constant_time_eq(expected.as_bytes(), response.as_bytes()) {
+            return Err(challenge());
+        }
+
+        // The response was computed with the right password, so a nonce which has merely expired
+        // only needs to be replaced.
+        // https://tools.ietf.org/html/rfc7616#section-3.3
+        let age = match self.nonce_age(nonce) {
+            Some(age) if age <= NONCE_LIFETIME => age,
+            Some(_) => return Err(self.challenge(UriAuthenticationMethod::Digest, true)),
+            None => return Err(challenge()),
+        };
+        // Clients which predate qop send no nonce-count, so each of their nonces is good for one
+        // request only.
+        let count = match param("nc") {
+            Some(nc) => u32::from_str_radix(nc, 16).map_err(|_| challenge())?,
+            None => u32::MAX,
+        };
+        if self.use_nonce(nonce, count, NONCE_LIFETIME - age) {
+            Ok(String::from(user))
+        } else {
+            Err(challenge())
+        }
+    }
+
+    // Records that a nonce was used with the given nonce-count, which has to be higher than any it
+    // was used with before.
+    // https://tools.ietf.org/html/rfc7616#section-3.4
+    fn use_nonce(&self, nonce: &str, count: u32, lifetime: Duration) -> bool {
+        let now = Instant::now();
+        let mut nonce_counts = self.nonce_counts.lock().unwrap();
+        nonce_counts.retain(|_, used| used.expires > now);
+        match nonce_counts.get_mut(nonce) {
+            Some(used) if used.count >= count => false,
+            Some(used) => {
+                used.count = count;
+                true
+            }
+            None => {
+                nonce_counts.insert(
+                    String::from(nonce),
+                    NonceCount {
+                        expires: now + lifetime,
+                        count,
+                    },
+                );
+                true
+            }
+        }
+    }
+
+    // https://tools.ietf.org/html/rfc7617#section-2
+    // https://tools.ietf.org/html/rfc7616#section-3.3
+    pub fn challenge(&self, scheme: UriAuthenticationMethod, stale: bool) -> String {
+        match scheme {
+            UriAuthenticationMethod::Digest => format!(
+                "Digest realm=\"{}\", qop=\"auth\", algorithm=MD5, nonce=\"{}\"{}",
+                REALM,
+                self.nonce(),
+                if stale { ", stale=true" } else { "" }
+            ),
+            _ => format!("Basic realm=\"{}\", charset=\"UTF-8\"", REALM),
+        }
+    }
+
+    // The creation time in hex followed by its signature.
+    fn nonce(&self) -> String {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let created = format!("{:016x}", created);
+        let tag = hmac::sign(&self.nonce_key, created.as_bytes());
+        format!("{}{}", created, hex(tag.as_ref()))
+    }
+
+    // Returns how long ago a nonce was made, or None if this server did not make it.
+    fn nonce_age(&self, nonce: &str) -> Option<Duration> {
+        if nonce.len() < 16 || !nonce.is_char_boundary(16) {
+            return None;
+        }
+        let (created, tag) = nonce.split_at(16);
+        let expected = hmac::sign(&self.nonce_key, created.as_bytes());
+        if !htpasswd::constant_time_eq(hex(expected.as_ref()).as_bytes(), tag.as_bytes()) {
+            return None;
+        }
+        let created = UNIX_EPOCH + Duration::from_secs(u64::from_str_radix(created, 16).ok()?);
+        Some(
+            SystemTime::now()
+                .duration_since(created)
+                .unwrap_or_default(),
+        )
+    }
+}
+
+// Splits the comma separated name=value pairs of a Digest Authorization header. Values may be
+// quoted, in which case they may contain commas and backslash escapes.
+// https://tools.ietf.org/html/rfc7235#section-2.1
+fn parse_params(credentials: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let mut chars = credentials.chars().peekable();
+    loop {
+        while let Some(c) = chars.peek() {
+            if *c == ',' || c.is_whitespace() {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        let name: String = chars.by_ref().take_while(|&c| c != '=').collect();
+        if name.is_empty() {
+            break;
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => value.extend(chars.next()),
+                    c => value.push(c),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c == ',' {
+                    break;
+                }
+                value.push(c);
+                chars.next();
+            }
+        }
+        params.insert(name.trim().to_lowercase(), String::from(value.trim()));
+    }
+    params
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const URI: &str = "/ipp/print";
+
+    fn authenticator() -> Authenticator {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let ha1 = md5::hex_digest(format!("alice:{}:secret", REALM).as_bytes());
+        writeln!(file, "alice:{}:{}", REALM, ha1).unwrap();
+        Authenticator::new(Users::load(file.path(), REALM).unwrap()).unwrap()
+    }
+
+    // Answers a challenge the way a client knowing alice's password would.
+    fn request(authenticator: &Authenticator, nonce: &str, nc: Option<&str>) -> Request<Body> {
+        let ha1 = authenticator.users.digest_secret("alice").unwrap();
+        let ha2 = md5::hex_digest(format!("POST:{}", URI).as_bytes());
+        let (response, qop) = match nc {
+            Some(nc) => (
+                md5::hex_digest(format!("{}:{}:{}:abcdef:auth:{}", ha1, nonce, nc, ha2).as_bytes()),
+                format!(", qop=auth, nc={}, cnonce=\"abcdef\"", nc),
+            ),
+            None => (
+                md5::hex_digest(format!("{}:{}:{}", ha1, nonce, ha2).as_bytes()),
+                String::new(),
+            ),
+        };
+        let authorization = format!(
+            "Digest username=\"alice\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", response=\"{}\"{}",
+            REALM, nonce, URI, response, qop
+        );
+        Request::post(URI)
+            .header(header::AUTHORIZATION, authorization)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn authenticate(authenticator: &Authenticator, req: &Request<Body>) -> Result<String, String> {
+        authenticator.authenticate(UriAuthenticationMethod::Digest, req)
+    }
+
+    #[test]
+    fn md5_matches_rfc_1321_test_suite() {
+        // https://tools.ietf.org/html/rfc1321#appendix-A.5
+        assert_eq!(md5::hex_digest(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(md5::hex_digest(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            md5::hex_digest(
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"
+            ),
+            "57edf4a22be3c955ac49da2e2107b67a"
+        );
+    }
+
+    #[test]
+    fn digest_response_cannot_be_replayed() {
+        let authenticator = authenticator();
+        let nonce = authenticator.nonce();
+        let req = request(&authenticator, &nonce, Some("00000001"));
+        assert_eq!(
+            authenticate(&authenticator, &req),
+            Ok(String::from("alice"))
+        );
+        assert!(authenticate(&authenticator, &req).is_err());
+
+        let req = request(&authenticator, &nonce, Some("00000002"));
+        assert_eq!(
+            authenticate(&authenticator, &req),
+            Ok(String::from("alice"))
+        );
+    }
+
+    #[test]
+    fn nonce_count_has_to_increase() {
+        let authenticator = authenticator();
+        let nonce = authenticator.nonce();
+        let req = request(&authenticator, &nonce, Some("00000005"));
+        assert!(authenticate(&authenticator, &req).is_ok());
+        let req = request(&authenticator, &nonce, Some("00000003"));
+        assert!(authenticate(&authenticator, &req).is_err());
+    }
+
+    #[test]
+    fn nonce_without_qop_is_used_once() {
+        let authenticator = authenticator();
+        let nonce = authenticator.nonce();
+        let req = request(&authenticator, &nonce, None);
+        assert!(authenticate(&authenticator, &req).is_ok());
+        assert!(authenticate(&authenticator, &req).is_err());
+    }
+
+    #[test]
+    fn wrong_password_is_refused() {
+        let authenticator = authenticator();
+        let nonce = authenticator.nonce();
+        let mut req = request(&authenticator, &nonce, Some("00000001"));
+        let authorization = req.headers()[header::AUTHORIZATION]
+            .to_str()
+            .unwrap()
+            .replace("response=\"", "response=\"0");
+        req.headers_mut()
+            .insert(header::AUTHORIZATION, authorization.parse().unwrap());
+        assert!(authenticate(&authenticator, &req).is_err());
+    }
+}
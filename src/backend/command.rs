@@ -0,0 +1,107 @@
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tokio::process::{Child, Command};
+
+use crate::backend::{Backend, BackendError, DeviceStatus};
+use crate::printer::{Document, Job};
+
+// Runs a command once for each document, writing the document to its standard input. The job's
+// attributes are passed in environment variables named after them, e.g. IPP_JOB_ID for job-id. A
+// command which exits unsuccessfully aborts the job, and one which is still running when the job is
+// canceled is killed.
+#[derive(Debug)]
+pub struct CommandBackend {
+    program: String,
+}
+
+impl CommandBackend {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: String::from(program),
+        }
+    }
+
+    // Returns whether the command ran to the end, rather than being killed because the job was
+    // stopped.
+    async fn run(
+        &self,
+        job: &Job,
+        document: &Document,
+        status: &DeviceStatus,
+    ) -> Result<bool, BackendError> {
+        let mut child = Command::new(&self.program)
+            .envs(environment(job, document))
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| BackendError::Unavailable(format!("{}: {}", self.program, e)))?;
+
+        let exit_status = tokio::select! {
+            result = feed(&mut child, document.path()) => Some(result?),
+            _ = status.stopped() => None,
+        };
+        match exit_status {
+            Some(exit_status) if !exit_status.success() => Err(BackendError::Failed(format!(
+                "{} {}",
+                self.program, exit_status
+            ))),
+            Some(_) => Ok(true),
+            None => {
+                child.kill().await?;
+                Ok(false)
+            }
+        }
+    }
+
+    async fn run_all(&self, job: &Job, status: &DeviceStatus) -> Result<(), BackendError> {
+        for document in &job.documents {
+            if !self.run(job, document, status).await? {
+                println!("Killed {} for Job {}", self.program, job.uri);
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Backend for CommandBackend {
+    fn print<'a>(
+        &'a self,
+        job: &'a Job,
+        status: &'a DeviceStatus,
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        self.run_all(job, status).boxed()
+    }
+}
+
+// Writes a document to the command's standard input and waits for it to exit.
+async fn feed(child: &mut Child, path: &Path) -> std::io::Result<ExitStatus> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut stdin = child.stdin.take().unwrap();
+    match tokio::io::copy(&mut file, &mut stdin).await {
+        // The command may finish without reading all of its input; its exit status decides
+        // whether that was a failure.
+        Err(e) if e.kind() != ErrorKind::BrokenPipe => return Err(e),
+        _ => {}
+    }
+    drop(stdin);
+    child.wait().await
+}
+
+fn environment(job: &Job, document: &Document) -> Vec<(&'static str, String)> {
+    vec![
+        ("IPP_JOB_ID", job.id.to_string()),
+        ("IPP_JOB_URI", job.uri.clone()),
+        ("IPP_JOB_NAME", job.name.clone()),
+        (
+            "IPP_JOB_ORIGINATING_USER_NAME",
+            job.originating_user_name.clone(),
+        ),
+        ("IPP_NUMBER_OF_DOCUMENTS", job.documents.len().to_string()),
+        ("IPP_DOCUMENT_NUMBER", document.number.to_string()),
+        ("IPP_DOCUMENT_FORMAT", String::from(document.format)),
+    ]
+}
@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::Serialize;
+
+use crate::backend::{Backend, BackendError, DeviceStatus};
+use crate::printer::{Document, Job, MimeMediaType};
+
+// Writes each document to a spool directory, alongside a JSON sidecar describing the job it belongs
+// to. The sidecar is written after the document, so anything watching the directory for sidecars
+// will only see complete documents.
+#[derive(Debug)]
+pub struct DirectoryBackend {
+    path: PathBuf,
+}
+
+// The contents of a document's sidecar, named after the corresponding IPP attributes.
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Metadata<'a> {
+    job_id: u32,
+    job_uri: &'a str,
+    job_name: &'a str,
+    job_originating_user_name: &'a str,
+    number_of_documents: usize,
+    time_at_creation: String,
+    document_number: u32,
+    document_format: String,
+    document_octets: u64,
+}
+
+impl DirectoryBackend {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    async fn write(&self, job: &Job) -> Result<(), BackendError> {
+        tokio::fs::create_dir_all(&self.path)
+            .await
+            .map_err(|e| BackendError::Unavailable(format!("{}: {}", self.path.display(), e)))?;
+        for document in &job.documents {
+            let name = format!("job-{}-document-{}", job.id, document.number);
+            let data_path = self
+                .path
+                .join(format!("{}.{}", name, extension(document.format)));
+            tokio::fs::copy(document.path(), &data_path).await?;
+
+            let metadata = serde_json::to_vec_pretty(&metadata(job, document))
+                .map_err(|e| BackendError::Failed(e.to_string()))?;
+            tokio::fs::write(self.path.join(format!("{}.json", name)), metadata).await?;
+            println!("Wrote Document: {}", data_path.display());
+        }
+        Ok(())
+    }
+}
+
+impl Backend for DirectoryBackend {
+    fn print<'a>(
+        &'a self,
+        job: &'a Job,
+        _status: &'a DeviceStatus,
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        self.write(job).boxed()
+    }
+}
+
+fn metadata<'a>(job: &'a Job, document: &Document) -> Metadata<'a> {
+    Metadata {
+        job_id: job.id,
+        job_uri: &job.uri,
+        job_name: &job.name,
+        job_originating_user_name: &job.originating_user_name,
+        number_of_documents: job.documents.len(),
+        time_at_creation: DateTime::<Utc>::from(job.time_at_creation().system_time()).to_rfc3339(),
+        document_number: document.number,
+        document_format: String::from(document.format),
+        document_octets: document.size(),
+    }
+}
+
+fn extension(format: MimeMediaType) -> &'static str {
+    match format {
+        MimeMediaType::Html => "html",
+        MimeMediaType::PlainText
+        | MimeMediaType::PlainTextAscii
+        | MimeMediaType::PlainTextLatin1
+        | MimeMediaType::PlainTextUtf8 => "txt",
+        MimeMediaType::Postscript => "ps",
+        MimeMediaType::HpPcl => "pcl",
+        MimeMediaType::Pdf => "pdf",
+        MimeMediaType::PwgRaster => "pwg",
+        MimeMediaType::Urf => "urf",
+        MimeMediaType::Jpeg => "jpg",
+        MimeMediaType::Png => "png",
+        MimeMediaType::AutoSense => "bin",
+    }
+}
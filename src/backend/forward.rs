@@ -0,0 +1,384 @@
+use std::convert::TryFrom;
+use std::io;
+use std::time::Duration;
+
+use futures::channel::mpsc;
+use futures::future::BoxFuture;
+use futures::{FutureExt, SinkExt, TryStreamExt};
+use ipp::prelude::*;
+use log::warn;
+use tokio::io::AsyncReadExt;
+
+use crate::backend::{Backend, BackendError, DeviceStatus};
+use crate::printer::{Document, Job, PrinterStateReason, PrinterStateReasonKeyword};
+
+// How many times an unreachable upstream printer is tried before the job is aborted.
+const CONNECT_ATTEMPTS: u32 = 6;
+// How long to wait between attempts to reach the upstream printer.
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+// How often the upstream job is checked while it is being printed.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+// How much of a document is read at a time while it is sent upstream.
+const CHUNK_SIZE: usize = 64 * 1024;
+// https://tools.ietf.org/html/rfc8010#section-4.2
+const DEFAULT_PORT: u16 = 631;
+
+// Forwards each job to an upstream IPP printer and follows it there until it is finished. The state
+// of the upstream job and printer is reflected onto our own while the job is being processed.
+#[derive(Debug)]
+pub struct ForwardingBackend {
+    // The upstream printer's URI with an http or https scheme, as requests are sent to it.
+    uri: Uri,
+}
+
+impl ForwardingBackend {
+    // Accepts an ipp:// or ipps:// printer URI.
+    // https://tools.ietf.org/html/rfc3510
+    pub fn new(printer_uri: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid IPP printer URI {}", printer_uri);
+        let uri: Uri = printer_uri.parse().map_err(|_| invalid())?;
+        let scheme = match uri.scheme_str().map(|s| s.to_lowercase()).as_deref() {
+            Some("ipp") => "http",
+            Some("ipps") => "https",
+            _ => return Err(invalid()),
+        };
+        let host = uri.host().ok_or_else(invalid)?;
+        let port = uri.port_u16().unwrap_or(DEFAULT_PORT);
+        let uri = format!("{}://{}:{}{}", scheme, host, port, uri.path())
+            .parse()
+            .map_err(|_| invalid())?;
+        Ok(Self { uri })
+    }
+
+    async fn forward(&self, job: &Job, status: &DeviceStatus) -> Result<(), BackendError> {
+        let mut attempt = 1;
+        let upstream_job_id = loop {
+            match self.submit(job).await {
+                Err(BackendError::Unavailable(reason)) if attempt < CONNECT_ATTEMPTS => {
+                    warn!("{} is unavailable: {}", self.uri, reason);
+                    println!("Retrying Job {} in {:?}", job.uri, RETRY_DELAY);
+                    status.set_printer_state_reasons(vec![
+                        PrinterStateReasonKeyword::ConnectingToDevice.into(),
+                    ]);
+                    // A job canceled while the upstream printer is unreachable is not retried.
+                    tokio::select! {
+                        _ = tokio::time::sleep(RETRY_DELAY) => attempt += 1,
+                        _ = status.stopped() => {
+                            println!("Stopped retrying Job {}", job.uri);
+                            return Ok(());
+                        }
+                    }
+                }
+                result => break result?,
+            }
+        };
+        println!(
+            "Forwarded Job {} to {} as job {}",
+            job.uri, self.uri, upstream_job_id
+        );
+        status.set_printer_state_reasons(Vec::new());
+        self.follow(job, upstream_job_id, status).await
+    }
+
+    // Submits the job with Print-Job, or with Create-Job and Send-Document if it has several
+    // documents. Returns the upstream job-id. If a document cannot be sent, the upstream job is
+    // canceled so that submitting the job again does not leave it behind.
+    async fn submit(&self, job: &Job) -> Result<i32, BackendError> {
+        if let [document] = job.documents.as_slice() {
+            let operation =
+                IppOperationBuilder::print_job(self.uri.clone(), payload(document).await?)
+                    .user_name(&job.originating_user_name)
+                    .job_title(&job.name)
+                    .build();
+            let mut req: IppRequestResponse = operation.into();
+            add_document_format(&mut req, document);
+            let response = self.send(req).await?;
+            return job_id(&response);
+        }
+
+        let operation = IppOperationBuilder::create_job(self.uri.clone())
+            .job_name(&job.name)
+            .build();
+        let mut req: IppRequestResponse = operation.into();
+        req.attributes_mut().add(
+            DelimiterTag::OperationAttributes,
+            IppAttribute::new(
+                IppAttribute::REQUESTING_USER_NAME,
+                IppValue::NameWithoutLanguage(job.originating_user_name.clone()),
+            ),
+        );
+        let upstream_job_id = job_id(&self.send(req).await?)?;
+        if let Err(e) = self.send_documents(job, upstream_job_id).await {
+            println!("Canceling partly sent upstream job {}", upstream_job_id);
+            self.cancel(job, upstream_job_id).await?;
+            return Err(e);
+        }
+        Ok(upstream_job_id)
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-4.3.1
+    async fn send_documents(&self, job: &Job, upstream_job_id: i32) -> Result<(), BackendError> {
+        for document in &job.documents {
+            let last = document.number as usize == job.documents.len();
+            let operation = IppOperationBuilder::send_document(
+                self.uri.clone(),
+                upstream_job_id,
+                payload(document).await?,
+            )
+            .user_name(&job.originating_user_name)
+            .last(last)
+            .build();
+            let mut req: IppRequestResponse = operation.into();
+            add_document_format(&mut req, document);
+            self.send(req).await?;
+        }
+        Ok(())
+    }
+
+    // Polls the upstream job until it finishes, reporting its progress along the way. A job which
+    // is canceled here is canceled upstream too.
+    async fn follow(
+        &self,
+        job: &Job,
+        upstream_job_id: i32,
+        status: &DeviceStatus,
+    ) -> Result<(), BackendError> {
+        let mut failures = 0;
+        loop {
+            if status.is_stopped() {
+                println!("Canceling upstream job {}", upstream_job_id);
+                return self.cancel(job, upstream_job_id).await;
+            }
+
+            match self.poll(job, upstream_job_id, status).await {
+                Ok(Some(result)) => return result,
+                Ok(None) => failures = 0,
+                Err(BackendError::Unavailable(reason)) if failures + 1 < CONNECT_ATTEMPTS => {
+                    warn!("{} is unavailable: {}", self.uri, reason);
+                    status.set_printer_state_reasons(vec![
+                        PrinterStateReasonKeyword::ConnectingToDevice.into(),
+                    ]);
+                    failures += 1;
+                }
+                Err(e) => return Err(e),
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    // Returns the outcome of the upstream job once it has finished.
+    // https://tools.ietf.org/html/rfc8011#section-5.3.7
+    async fn poll(
+        &self,
+        job: &Job,
+        upstream_job_id: i32,
+        status: &DeviceStatus,
+    ) -> Result<Option<Result<(), BackendError>>, BackendError> {
+        let operation = IppOperationBuilder::get_printer_attributes(self.uri.clone())
+            .attribute(IppAttribute::PRINTER_STATE_REASONS)
+            .build();
+        let response = self.send(operation.into()).await?;
+        let reasons = attribute(
+            &response,
+            DelimiterTag::PrinterAttributes,
+            "printer-state-reasons",
+        )
+        .map(keywords)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|keyword| PrinterStateReason::try_from(keyword.as_str()).ok())
+        .collect();
+        status.set_printer_state_reasons(reasons);
+
+        let req = self.job_request(Operation::GetJobAttributes, job, upstream_job_id);
+        let response = self.send(req).await?;
+        let state = match attribute(&response, DelimiterTag::JobAttributes, "job-state") {
+            Some(IppValue::Enum(state)) => *state,
+            _ => {
+                return Err(BackendError::Failed(String::from(
+                    "Upstream job has no job-state",
+                )))
+            }
+        };
+        let outcome = match state {
+            // pending, pending-held
+            3 | 4 => {
+                status.set_job_queued(true);
+                None
+            }
+            // processing, processing-stopped
+            5 | 6 => {
+                status.set_job_queued(false);
+                None
+            }
+            7 => Some(Err(BackendError::Canceled(format!(
+                "Upstream job {} was canceled",
+                upstream_job_id
+            )))),
+            8 => Some(Err(BackendError::Failed(format!(
+                "Upstream job {} was aborted",
+                upstream_job_id
+            )))),
+            9 => Some(Ok(())),
+            _ => {
+                return Err(BackendError::Failed(format!(
+                    "Upstream job has unknown job-state {}",
+                    state
+                )))
+            }
+        };
+        Ok(outcome)
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-4.3.3
+    async fn cancel(&self, job: &Job, upstream_job_id: i32) -> Result<(), BackendError> {
+        let req = self.job_request(Operation::CancelJob, job, upstream_job_id);
+        match self.send(req).await {
+            Ok(_) => Ok(()),
+            // The upstream job may have finished in the meantime.
+            Err(e) => {
+                warn!("Could not cancel upstream job {}: {}", upstream_job_id, e);
+                Ok(())
+            }
+        }
+    }
+
+    // A request for the upstream job, made on behalf of the job's owner so that an upstream policy
+    // which keeps jobs to their owners lets it through.
+    fn job_request(
+        &self,
+        operation: Operation,
+        job: &Job,
+        upstream_job_id: i32,
+    ) -> IppRequestResponse {
+        let mut req =
+            IppRequestResponse::new(IppVersion::v1_1(), operation, Some(self.uri.clone()));
+        req.attributes_mut().add(
+            DelimiterTag::OperationAttributes,
+            IppAttribute::new(IppAttribute::JOB_ID, IppValue::Integer(upstream_job_id)),
+        );
+        req.attributes_mut().add(
+            DelimiterTag::OperationAttributes,
+            IppAttribute::new(
+                IppAttribute::REQUESTING_USER_NAME,
+                IppValue::NameWithoutLanguage(job.originating_user_name.clone()),
+            ),
+        );
+        req
+    }
+
+    // Sends a request upstream. Failures which may clear up by themselves, such as the printer
+    // being unreachable or busy, are reported as BackendError::Unavailable.
+    async fn send(&self, req: IppRequestResponse) -> Result<IppRequestResponse, BackendError> {
+        let client = IppClient::builder(self.uri.clone())
+            .request_timeout(REQUEST_TIMEOUT)
+            .build();
+        let response = client.send_request(req).await.map_err(|e| match e {
+            IppError::ClientError(_) | IppError::IOError(_) => {
+                BackendError::Unavailable(e.to_string())
+            }
+            IppError::RequestError(status) if status >= 500 => {
+                BackendError::Unavailable(e.to_string())
+            }
+            _ => BackendError::Failed(e.to_string()),
+        })?;
+
+        let status = response.header().operation_status;
+        match StatusCode::from_u16(status) {
+            Some(StatusCode::SuccessfulOK)
+            | Some(StatusCode::SuccessfulOKIgnoredOrSubstitutedAttributes)
+            | Some(StatusCode::SuccessfulOKConflictingAttributes) => Ok(response),
+            Some(code @ StatusCode::ServerErrorServiceUnavailable)
+            | Some(code @ StatusCode::ServerErrorTemporaryError)
+            | Some(code @ StatusCode::ServerErrorNotAcceptingJobs)
+            | Some(code @ StatusCode::ServerErrorBusy) => {
+                Err(BackendError::Unavailable(code.to_string()))
+            }
+            Some(code @ StatusCode::ClientErrorDocumentFormatNotSupported)
+            | Some(code @ StatusCode::ClientErrorDocumentFormatError)
+            | Some(code @ StatusCode::ClientErrorCompressionNotSupported)
+            | Some(code @ StatusCode::ClientErrorCompressionError) => {
+                Err(BackendError::DocumentRejected(code.to_string()))
+            }
+            Some(code) => Err(BackendError::Failed(code.to_string())),
+            None => Err(BackendError::Failed(format!(
+                "Unknown status {:#06x}",
+                status
+            ))),
+        }
+    }
+}
+
+impl Backend for ForwardingBackend {
+    fn print<'a>(
+        &'a self,
+        job: &'a Job,
+        status: &'a DeviceStatus,
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        self.forward(job, status).boxed()
+    }
+}
+
+// The IPP client sends requests from its own thread, outside the Tokio runtime, so the document is
+// read by a task on the runtime and handed over a chunk at a time.
+async fn payload(document: &Document) -> Result<IppPayload, BackendError> {
+    let mut file = tokio::fs::File::open(document.path()).await?;
+    let (mut chunks, received) = mpsc::channel::<io::Result<Vec<u8>>>(1);
+    tokio::spawn(async move {
+        let mut buf = vec![0; CHUNK_SIZE];
+        loop {
+            let chunk = match file.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(len) => Ok(buf[..len].to_vec()),
+                Err(e) => Err(e),
+            };
+            let failed = chunk.is_err();
+            // The request has been given up if the receiving end is gone.
+            if chunks.send(chunk).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    Ok(IppPayload::new(received.into_async_read()))
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.2.1.1
+fn add_document_format(req: &mut IppRequestResponse, document: &Document) {
+    req.attributes_mut().add(
+        DelimiterTag::OperationAttributes,
+        IppAttribute::new(
+            "document-format",
+            IppValue::MimeMediaType(String::from(document.format)),
+        ),
+    );
+}
+
+fn attribute<'a>(
+    response: &'a IppRequestResponse,
+    tag: DelimiterTag,
+    name: &str,
+) -> Option<&'a IppValue> {
+    response
+        .attributes()
+        .groups_of(tag)
+        .find_map(|group| group.attributes().get(name))
+        .map(|attr| attr.value())
+}
+
+fn keywords(value: &IppValue) -> Vec<String> {
+    match value {
+        IppValue::Keyword(keyword) => vec![keyword.clone()],
+        IppValue::Array(values) => values.iter().flat_map(keywords).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn job_id(response: &IppRequestResponse) -> Result<i32, BackendError> {
+    match attribute(response, DelimiterTag::JobAttributes, "job-id") {
+        Some(IppValue::Integer(job_id)) => Ok(*job_id),
+        _ => Err(BackendError::Failed(String::from(
+            "Upstream printer did not return a job-id",
+        ))),
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+
+pub use crate::backend::command::CommandBackend;
+pub use crate::backend::directory::DirectoryBackend;
+pub use crate::backend::forward::ForwardingBackend;
+pub use crate::backend::null::NullBackend;
+pub use crate::backend::socket::SocketBackend;
+use crate::printer::{Job, JobStateReason, Printer, PrinterStateReason};
+
+mod command;
+mod directory;
+mod forward;
+mod null;
+mod socket;
+
+// How often a backend in the middle of a transfer looks to see whether its job has been stopped.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub enum BackendError {
+    // The output device could not be reached or started.
+    Unavailable(String),
+    // The output device could not process a document.
+    DocumentRejected(String),
+    // The job was canceled at the output device.
+    Canceled(String),
+    // The document could not be delivered.
+    Failed(String),
+}
+
+impl BackendError {
+    // The reason reported for a job aborted because of this error.
+    // https://tools.ietf.org/html/rfc8011#section-5.3.8
+    pub fn job_state_reason(&self) -> JobStateReason {
+        match self {
+            BackendError::Unavailable(_) => JobStateReason::ServiceOffLine,
+            BackendError::DocumentRejected(_) => JobStateReason::DocumentFormatError,
+            BackendError::Canceled(_) => JobStateReason::JobCanceledAtDevice,
+            BackendError::Failed(_) => JobStateReason::AbortedBySystem,
+        }
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::Unavailable(reason) => write!(f, "Output device unavailable: {}", reason),
+            BackendError::DocumentRejected(reason) => write!(f, "Document rejected: {}", reason),
+            BackendError::Canceled(reason) => write!(f, "Job canceled: {}", reason),
+            BackendError::Failed(reason) => write!(f, "Output failed: {}", reason),
+        }
+    }
+}
+
+impl From<std::io::Error> for BackendError {
+    fn from(e: std::io::Error) -> Self {
+        BackendError::Failed(e.to_string())
+    }
+}
+
+// Delivers the documents of a job which is being processed to the output device.
+pub trait Backend: fmt::Debug + Send + Sync {
+    fn print<'a>(
+        &'a self,
+        job: &'a Job,
+        status: &'a DeviceStatus,
+    ) -> BoxFuture<'a, Result<(), BackendError>>;
+}
+
+// Lets a backend report the state of the output device while it processes a job.
+pub struct DeviceStatus {
+    mx_printer: Arc<RwLock<Printer>>,
+    job_id: u32,
+}
+
+impl DeviceStatus {
+    pub fn new(mx_printer: Arc<RwLock<Printer>>, job_id: u32) -> Self {
+        Self { mx_printer, job_id }
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-5.4.12
+    pub fn set_printer_state_reasons(&self, reasons: Vec<PrinterStateReason>) {
+        let mut printer = self.mx_printer.write().unwrap();
+        printer.set_device_state_reasons(reasons);
+    }
+
+    pub fn set_job_queued(&self, queued: bool) {
+        let mut printer = self.mx_printer.write().unwrap();
+        if let Some(job) = printer.job_mut(self.job_id) {
+            job.set_queued_in_device(queued);
+        }
+    }
+
+    // Whether the job has been canceled, or otherwise stopped, since it was handed to the backend.
+    pub fn is_stopped(&self) -> bool {
+        let printer = self.mx_printer.read().unwrap();
+        printer
+            .job(self.job_id)
+            .map(|job| job.state().is_terminal())
+            .unwrap_or(true)
+    }
+
+    // Completes once the job has been stopped, so that a transfer can be given up part way through.
+    pub async fn stopped(&self) {
+        while !self.is_stopped() {
+            tokio::time::sleep(STOP_POLL_INTERVAL).await;
+        }
+    }
+}
+
+// Creates the backend for a device URI, in the style of CUPS' device-uri:
+//
+//   directory:/var/spool/print-srv   write each document to a directory
+//   command:/usr/local/bin/archive   pipe each document to a command
+//   ipp://printer.example/ipp/print  forward each job to an IPP printer
+//   socket://printer.example:9100    send each document to an AppSocket (JetDirect) port
+//   null:                            discard each document
+pub fn from_device_uri(uri: &str) -> Result<Box<dyn Backend>, String> {
+    let (scheme, rest) = uri
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid device URI {}", uri))?;
+    match scheme.to_lowercase().as_str() {
+        "directory" if !rest.is_empty() => Ok(Box::new(DirectoryBackend::new(rest))),
+        "command" if !rest.is_empty() => Ok(Box::new(CommandBackend::new(rest))),
+        "ipp" | "ipps" => Ok(Box::new(ForwardingBackend::new(uri)?)),
+        "socket" => Ok(Box::new(SocketBackend::new(uri)?)),
+        "null" => Ok(Box::new(NullBackend)),
+        _ => Err(format!("Unsupported device URI {}", uri)),
+    }
+}
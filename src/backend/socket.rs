@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use log::warn;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+use crate::backend::{Backend, BackendError, DeviceStatus};
+use crate::printer::{Document, Job, PrinterStateReasonKeyword};
+
+// How many times an unreachable printer is tried before the job is aborted.
+const CONNECT_ATTEMPTS: u32 = 6;
+// How long to wait between attempts to reach the printer.
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+// How long the printer may take to accept each part of a document before the job is aborted.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(300);
+const DEFAULT_PORT: u16 = 9100;
+// How much of a document is written at a time.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+// Sends each document, unaltered, to a printer's AppSocket (JetDirect) port. The printer is
+// expected to understand the document format itself.
+#[derive(Debug)]
+pub struct SocketBackend {
+    // host:port
+    address: String,
+}
+
+impl SocketBackend {
+    // Accepts socket://host or socket://host:port.
+    pub fn new(device_uri: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid socket device URI {}", device_uri);
+        let (scheme, rest) = device_uri.split_once("://").ok_or_else(invalid)?;
+        if !scheme.eq_ignore_ascii_case("socket") {
+            return Err(invalid());
+        }
+        let authority = rest.split('/').next().unwrap_or_default();
+        if authority.is_empty() {
+            return Err(invalid());
+        }
+        let has_port = match authority.rsplit_once(':') {
+            // A bracketed IPv6 address without a port still contains colons.
+            Some((host, port)) => !port.contains(']') && !host.is_empty(),
+            None => false,
+        };
+        let address = if has_port {
+            String::from(authority)
+        } else {
+            format!("{}:{}", authority, DEFAULT_PORT)
+        };
+        Ok(Self { address })
+    }
+
+    async fn send_all(&self, job: &Job, status: &DeviceStatus) -> Result<(), BackendError> {
+        for document in &job.documents {
+            let stream = self.connect(status).await?;
+            // Dropping the connection part way through is all it takes to stop a canceled job.
+            tokio::select! {
+                result = self.send(stream, document) => result?,
+                _ = status.stopped() => {
+                    println!("Stopped sending Job {} to {}", job.uri, self.address);
+                    return Ok(());
+                }
+            }
+            println!(
+                "Sent Document {} of Job {} to {}",
+                document.number, job.uri, self.address
+            );
+        }
+        Ok(())
+    }
+
+    async fn connect(&self, status: &DeviceStatus) -> Result<TcpStream, BackendError> {
+        let mut attempt = 1;
+        loop {
+            let error = match timeout(CONNECT_TIMEOUT, TcpStream::connect(&self.address)).await {
+                Ok(Ok(stream)) => {
+                    status.set_printer_state_reasons(Vec::new());
+                    return Ok(stream);
+                }
+                Ok(Err(e)) => e.to_string(),
+                Err(_) => String::from("connection timed out"),
+            };
+            if attempt >= CONNECT_ATTEMPTS {
+                return Err(BackendError::Unavailable(format!(
+                    "{}: {}",
+                    self.address, error
+                )));
+            }
+            warn!("{} is unavailable: {}", self.address, error);
+            status.set_printer_state_reasons(vec![
+                PrinterStateReasonKeyword::ConnectingToDevice.into()
+            ]);
+            tokio::time::sleep(RETRY_DELAY).await;
+            attempt += 1;
+        }
+    }
+
+    // Each write is timed on its own, so a large document sent over a slow link is not cut off as
+    // long as the printer keeps accepting data.
+    async fn send(&self, mut stream: TcpStream, document: &Document) -> Result<(), BackendError> {
+        let stalled = |_| BackendError::Failed(format!("{} stopped accepting data", self.address));
+        let mut file = tokio::fs::File::open(document.path()).await?;
+        let mut buf = vec![0u8; BUFFER_SIZE];
+        loop {
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            timeout(WRITE_TIMEOUT, stream.write_all(&buf[..n]))
+                .await
+                .map_err(stalled)??;
+        }
+        timeout(WRITE_TIMEOUT, stream.shutdown())
+            .await
+            .map_err(stalled)??;
+        Ok(())
+    }
+}
+
+impl Backend for SocketBackend {
+    fn print<'a>(
+        &'a self,
+        job: &'a Job,
+        status: &'a DeviceStatus,
+    ) -> BoxFuture<'a, Result<(), BackendError>> {
+        self.send_all(job, status).boxed()
+    }
+}
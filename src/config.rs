@@ -0,0 +1,528 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio_rustls::rustls::ServerConfig;
+
+use crate::auth::{self, Authenticator, Users};
+use crate::backend;
+use crate::fetcher::{Fetchers, FileFetcher, HttpFetcher};
+use crate::policy::Policy;
+use crate::printer::{
+    Charset, IppVersion, MimeMediaType, NaturalLanguage, PdlOverride, Printer, PrinterUri,
+    UriAuthenticationMethod, UriSecurityMethod,
+};
+use crate::spool::{InterruptedJobs, Spool};
+use crate::tls;
+
+// The resource path clients send requests to.
+pub const PRINTER_PATH: &str = "/ipp/print";
+
+// The printer definition read from a TOML file. Anything left out keeps the value of
+// Printer::default(), and without any listen addresses the server listens for IPP on port 3000.
+//
+//     name = "Office Printer"
+//     hostname = "print.example.com"
+//     device-uri = "directory:/var/spool/print-srv"
+//     document-format-supported = ["application/pdf", "text/plain"]
+//     document-format-default = "application/pdf"
+//     pdl-override-supported = "not-attempted"
+//     ipp-versions-supported = ["1.1", "2.0"]
+//     max-document-size = 536870912
+//     job-retention = 86400
+//     spool = "/var/lib/print-srv"
+//     interrupted-jobs = "requeue"
+//
+//     [listen]
+//     ipp = ["0.0.0.0:631"]
+//     ipps = ["0.0.0.0:443"]
+//     raw = "0.0.0.0:9100"
+//     lpd = "0.0.0.0:515"
+//
+//     [tls]
+//     cert = "/etc/print-srv/cert.pem"
+//     key = "/etc/print-srv/key.pem"
+//
+//     [fetch]
+//     schemes = ["file", "https"]
+//     file-root = "/srv/documents"
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    name: Option<String>,
+    // The host name used in printer URIs for listeners bound to all addresses. Defaults to the
+    // system's host name.
+    hostname: Option<String>,
+    // Where processed jobs are sent; see backend::from_device_uri.
+    device_uri: Option<String>,
+    listen: Listen,
+    // Where Print-URI and Send-URI may fetch documents from. Nothing is fetched unless it is
+    // configured here.
+    fetch: Fetch,
+    // The certificate for the ipps listeners. A self-signed one is made if this is left out.
+    tls: Option<Tls>,
+    // An htpasswd -s or htdigest file. Clients must then log in to print.
+    users: Option<PathBuf>,
+    // A policy file; see policy::Policy.
+    policy: Option<PathBuf>,
+    // A directory where jobs are kept so that they survive a restart; see spool::Spool. Without
+    // one, documents are kept in a temporary directory and jobs are lost when the server stops.
+    spool: Option<PathBuf>,
+    // Whether jobs which were being processed when the server stopped are aborted or requeued.
+    interrupted_jobs: InterruptedJobs,
+    // The largest document, in octets, which will be accepted.
+    max_document_size: Option<u64>,
+    // How many seconds completed, aborted and canceled jobs are kept before they are discarded.
+    job_retention: Option<u64>,
+    charset_configured: Option<String>,
+    charset_supported: Option<Vec<String>>,
+    document_format_default: Option<String>,
+    document_format_supported: Option<Vec<String>>,
+    // Whether a document which does not look like its document-format is printed as the format it
+    // looks like, or rejected.
+    pdl_override_supported: Option<String>,
+    generated_natural_language_supported: Option<Vec<String>>,
+    ipp_versions_supported: Option<Vec<String>>,
+    natural_language_configured: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Listen {
+    pub ipp: Vec<SocketAddr>,
+    pub ipps: Vec<SocketAddr>,
+    // Accepts jobs sent to an AppSocket (JetDirect) style port.
+    pub raw: Option<SocketAddr>,
+    // Accepts jobs from lpr and answers lpq.
+    pub lpd: Option<SocketAddr>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct Fetch {
+    // The reference-uri-schemes-supported: any of file, http and https.
+    schemes: Vec<String>,
+    // The only directory file URIs may refer to, along with everything beneath it. It must be
+    // given with the file scheme, and should hold nothing but documents clients may print.
+    file_root: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Tls {
+    cert: PathBuf,
+    key: PathBuf,
+}
+
+// What the server needs to start: the printer, and where to listen for it.
+pub struct Server {
+    pub printer: Printer,
+    pub listen: Listen,
+    pub tls_config: Option<Arc<ServerConfig>>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Builds the printer this configuration describes, checking every value along the way.
+    pub fn build(self) -> Result<Server, String> {
+        let mut printer = Printer::default();
+        if let Some(name) = self.name {
+            printer.printer_name = name;
+        }
+        if let Some(device_uri) = &self.device_uri {
+            printer.backend = Arc::from(backend::from_device_uri(device_uri)?);
+        }
+        if let Some(size) = self.max_document_size {
+            if size == 0 {
+                return Err(String::from("max-document-size must be greater than 0"));
+            }
+            printer.max_document_size = size;
+        }
+        if let Some(seconds) = self.job_retention {
+            printer.job_retention_period = Duration::from_secs(seconds);
+        }
+
+        if let Some(values) = &self.charset_supported {
+            printer.charset_supported = parse_all("charset-supported", values, |v| {
+                Charset::try_from(String::from(v))
+            })?;
+        }
+        if let Some(value) = &self.charset_configured {
+            printer.charset_configured = Charset::try_from(value.clone())
+                .map_err(|e| format!("charset-configured: {}", e))?;
+        }
+        if !printer
+            .charset_supported
+            .contains(&printer.charset_configured)
+        {
+            return Err(String::from(
+                "charset-configured must be one of charset-supported",
+            ));
+        }
+
+        if let Some(values) = &self.document_format_supported {
+            printer.document_format_supported =
+                parse_all("document-format-supported", values, |v| {
+                    MimeMediaType::try_from(v)
+                })?;
+        }
+        if let Some(value) = &self.document_format_default {
+            printer.document_format_default = MimeMediaType::try_from(value.as_str())
+                .map_err(|e| format!("document-format-default: {}", e))?;
+        }
+        if !printer
+            .document_format_supported
+            .contains(&printer.document_format_default)
+        {
+            return Err(String::from(
+                "document-format-default must be one of document-format-supported",
+            ));
+        }
+        if let Some(value) = &self.pdl_override_supported {
+            printer.pdl_override_supported = PdlOverride::try_from(value.as_str())
+                .map_err(|e| format!("pdl-override-supported: {}", e))?;
+        }
+
+        if let Some(values) = &self.ipp_versions_supported {
+            printer.ipp_versions_supported = parse_all("ipp-versions-supported", values, |v| {
+                IppVersion::try_from(v)
+            })?;
+        }
+        if let Some(values) = &self.generated_natural_language_supported {
+            printer.generated_natural_language_supported =
+                parse_all("generated-natural-language-supported", values, |v| {
+                    NaturalLanguage::try_from(v)
+                })?;
+        }
+        if let Some(value) = &self.natural_language_configured {
+            printer.natural_language_configured = NaturalLanguage::try_from(value.as_str())
+                .map_err(|e| format!("natural-language-configured: {}", e))?;
+        }
+
+        let mut listen = self.listen;
+        if listen.ipp.is_empty() && listen.ipps.is_empty() {
+            listen.ipp.push(SocketAddr::from(([0, 0, 0, 0], 3000)));
+        }
+        let hostname = match self.hostname {
+            Some(hostname) => hostname,
+            None => gethostname::gethostname().to_string_lossy().into_owned(),
+        };
+        printer.printer_uri_supported = printer_uris(&listen, &hostname);
+
+        let tls_config = if listen.ipps.is_empty() {
+            if self.tls.is_some() {
+                return Err(String::from(
+                    "[tls] is only used with ipps listen addresses",
+                ));
+            }
+            None
+        } else {
+            let mut hostnames = vec![
+                hostname,
+                String::from("localhost"),
+                String::from("127.0.0.1"),
+            ];
+            hostnames.extend(
+                listen
+                    .ipps
+                    .iter()
+                    .filter(|addr| !addr.ip().is_unspecified())
+                    .map(|addr| addr.ip().to_string()),
+            );
+            let tls = self.tls.as_ref();
+            Some(tls::server_config(
+                tls.map(|tls| tls.cert.as_path()),
+                tls.map(|tls| tls.key.as_path()),
+                hostnames,
+            )?)
+        };
+
+        if let Some(path) = &self.users {
+            let authenticator = Users::load(path, auth::REALM).and_then(Authenticator::new)?;
+            printer.authenticator = Some(Arc::new(authenticator));
+            // Passwords are only sent as they are over TLS; without it clients use Digest.
+            for uri in &mut printer.printer_uri_supported {
+                uri.authentication = match uri.security {
+                    UriSecurityMethod::Tls => UriAuthenticationMethod::Basic,
+                    UriSecurityMethod::None => UriAuthenticationMethod::Digest,
+                };
+            }
+        }
+        printer.set_fetchers(self.fetch.fetchers()?);
+        if let Some(path) = &self.policy {
+            printer.policy = Policy::load(path)?;
+        }
+        if let Some(path) = &self.spool {
+            let spool = Spool::open(path)?;
+            let (jobs, next_job_id) = spool
+                .recover(self.interrupted_jobs)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            println!("Recovered {} jobs from {}", jobs.len(), path.display());
+            printer.spool = Arc::new(spool);
+            printer.restore(jobs, next_job_id);
+        }
+
+        Ok(Server {
+            printer,
+            listen,
+            tls_config,
+        })
+    }
+}
+
+impl Fetch {
+    // https://tools.ietf.org/html/rfc8011#section-5.4.27
+    fn fetchers(&self) -> Result<Fetchers, String> {
+        let mut fetchers = Fetchers::default();
+        let mut http_schemes = Vec::new();
+        for scheme in &self.schemes {
+            match scheme.to_lowercase().as_str() {
+                "file" => {
+                    let root = self.file_root.as_ref().ok_or_else(|| {
+                        String::from("[fetch] file-root must be given with the file scheme")
+                    })?;
+                    let root = root
+                        .canonicalize()
+                        .map_err(|e| format!("{}: {}", root.display(), e))?;
+                    fetchers.register(Box::new(FileFetcher::new(root)));
+                }
+                scheme @ ("http" | "https") => http_schemes.push(String::from(scheme)),
+                _ => return Err(format!("[fetch] unsupported scheme {}", scheme)),
+            }
+        }
+        if self.file_root.is_some() && fetchers.schemes().is_empty() {
+            return Err(String::from(
+                "[fetch] file-root is only used with the file scheme",
+            ));
+        }
+        if !http_schemes.is_empty() {
+            fetchers.register(Box::new(HttpFetcher::new(http_schemes)));
+        }
+        Ok(fetchers)
+    }
+}
+
+fn parse_all<T>(
+    name: &str,
+    values: &[String],
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Vec<T>, String> {
+    if values.is_empty() {
+        return Err(format!("{} must not be empty", name));
+    }
+    values
+        .iter()
+        .map(|value| parse(value).map_err(|e| format!("{}: {}", name, e)))
+        .collect()
+}
+
+// One URI for each listener. Listeners bound to all addresses are reached by the host name; the
+// others by the address they are bound to.
+// https://tools.ietf.org/html/rfc8011#section-5.4.1
+fn printer_uris(listen: &Listen, hostname: &str) -> Vec<PrinterUri> {
+    let uri = |scheme: &str, addr: &SocketAddr| {
+        let host = match addr.ip() {
+            ip if ip.is_unspecified() => String::from(hostname),
+            IpAddr::V4(ip) => ip.to_string(),
+            IpAddr::V6(ip) => format!("[{}]", ip),
+        };
+        format!("{}://{}:{}{}", scheme, host, addr.port(), PRINTER_PATH)
+    };
+    let ipp = listen.ipp.iter().map(|addr| {
+        PrinterUri::new(
+            &uri("ipp", addr),
+            UriAuthenticationMethod::None,
+            UriSecurityMethod::None,
+        )
+    });
+    // https://tools.ietf.org/html/rfc7472#section-4
+    let ipps = listen.ipps.iter().map(|addr| {
+        PrinterUri::new(
+            &uri("ipps", addr),
+            UriAuthenticationMethod::None,
+            UriSecurityMethod::Tls,
+        )
+    });
+    ipp.chain(ipps).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::printer::Operation;
+
+    fn load(contents: &str) -> Result<Config, String> {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        Config::load(file.path())
+    }
+
+    fn build(contents: &str) -> Result<Server, String> {
+        load(contents)?.build()
+    }
+
+    #[test]
+    fn builds_printer_from_valid_file() {
+        let server = build(
+            r#"
+            name = "Office Printer"
+            hostname = "print.example.com"
+            device-uri = "null:"
+            document-format-supported = ["application/pdf", "text/plain"]
+            document-format-default = "text/plain"
+            ipp-versions-supported = ["1.1", "2.0"]
+            max-document-size = 1024
+            job-retention = 3600
+
+            [listen]
+            ipp = ["0.0.0.0:631", "127.0.0.1:8631"]
+            lpd = "127.0.0.1:515"
+            "#,
+        )
+        .unwrap();
+        let printer = &server.printer;
+        assert_eq!(printer.printer_name, "Office Printer");
+        assert_eq!(
+            printer.document_format_supported,
+            vec![MimeMediaType::Pdf, MimeMediaType::PlainText]
+        );
+        assert_eq!(printer.document_format_default, MimeMediaType::PlainText);
+        assert_eq!(
+            printer.ipp_versions_supported,
+            vec![IppVersion::V1_1, IppVersion::V2_0]
+        );
+        assert_eq!(printer.max_document_size, 1024);
+        assert_eq!(printer.job_retention_period, Duration::from_secs(3600));
+        let uris: Vec<&str> = printer
+            .printer_uri_supported
+            .iter()
+            .map(|uri| uri.uri.as_str())
+            .collect();
+        assert_eq!(
+            uris,
+            vec![
+                "ipp://print.example.com:631/ipp/print",
+                "ipp://127.0.0.1:8631/ipp/print"
+            ]
+        );
+        assert_eq!(server.listen.lpd, Some("127.0.0.1:515".parse().unwrap()));
+        assert!(server.listen.raw.is_none());
+        assert!(server.tls_config.is_none());
+    }
+
+    #[test]
+    fn empty_file_keeps_defaults() {
+        let server = build("").unwrap();
+        let default = Printer::default();
+        assert_eq!(server.printer.printer_name, default.printer_name);
+        assert_eq!(
+            server.printer.document_format_supported,
+            default.document_format_supported
+        );
+        assert_eq!(
+            server.printer.job_retention_period,
+            default.job_retention_period
+        );
+        assert_eq!(
+            server.listen.ipp,
+            vec![SocketAddr::from(([0, 0, 0, 0], 3000))]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(load("colour = true").is_err());
+        assert!(load("[listen]\nhttp = [\"0.0.0.0:80\"]").is_err());
+        assert!(load("[printer]\nname = \"x\"").is_err());
+    }
+
+    #[test]
+    fn rejects_values_of_the_wrong_type() {
+        assert!(load("name = 3").is_err());
+        assert!(load("max-document-size = \"big\"").is_err());
+        assert!(load("job-retention = -1").is_err());
+        assert!(load("document-format-supported = \"text/plain\"").is_err());
+        assert!(load("[listen]\nipp = [\"not an address\"]").is_err());
+        assert!(load("interrupted-jobs = \"retry\"").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let error = |contents| build(contents).err().unwrap();
+        assert!(
+            error("document-format-supported = [\"application/x-nothing\"]")
+                .starts_with("document-format-supported")
+        );
+        assert!(error("document-format-supported = []").contains("must not be empty"));
+        assert!(error(
+            "document-format-supported = [\"application/pdf\"]\ndocument-format-default = \"text/plain\""
+        )
+        .contains("must be one of document-format-supported"));
+        assert!(error("ipp-versions-supported = [\"3.0\"]").starts_with("ipp-versions-supported"));
+        assert!(error("max-document-size = 0").contains("greater than 0"));
+        assert!(error("device-uri = \"lpt1:\"").contains("Unsupported device URI"));
+        assert!(
+            error("pdl-override-supported = \"sometimes\"").starts_with("pdl-override-supported")
+        );
+        assert!(
+            error("[tls]\ncert = \"cert.pem\"\nkey = \"key.pem\"").contains("only used with ipps")
+        );
+    }
+
+    #[test]
+    fn fetches_nothing_by_default() {
+        let printer = build("").unwrap().printer;
+        assert!(printer.fetchers.schemes().is_empty());
+        assert!(!printer.operations_supported.contains(&Operation::PrintUri));
+        assert!(!printer.operations_supported.contains(&Operation::SendUri));
+    }
+
+    #[test]
+    fn fetches_configured_schemes() {
+        let root = tempfile::tempdir().unwrap();
+        let printer = build(&format!(
+            "[fetch]\nschemes = [\"file\", \"https\"]\nfile-root = {:?}",
+            root.path()
+        ))
+        .unwrap()
+        .printer;
+        assert_eq!(printer.fetchers.schemes(), vec!["file", "https"]);
+        assert!(printer.operations_supported.contains(&Operation::PrintUri));
+        assert!(printer.operations_supported.contains(&Operation::SendUri));
+    }
+
+    #[test]
+    fn rejects_invalid_fetch_settings() {
+        let error = |contents| build(contents).err().unwrap();
+        assert!(error("[fetch]\nschemes = [\"file\"]").contains("file-root must be given"));
+        assert!(error("[fetch]\nschemes = [\"gopher\"]").contains("unsupported scheme gopher"));
+        assert!(error("[fetch]\nschemes = [\"http\"]\nfile-root = \"/srv\"")
+            .contains("only used with the file scheme"));
+        assert!(
+            error("[fetch]\nschemes = [\"file\"]\nfile-root = \"/nonexistent/documents\"")
+                .starts_with("/nonexistent/documents")
+        );
+        assert!(load("[fetch]\nroot = \"/srv\"").is_err());
+    }
+
+    #[test]
+    fn reports_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.toml");
+        assert!(Config::load(&path)
+            .err()
+            .unwrap()
+            .starts_with(&path.display().to_string()));
+    }
+}
@@ -0,0 +1,392 @@
+use std::io;
+
+// The format written by the UNIX compress utility: a three octet header followed by LZW codes of
+// growing width, packed least significant bit first.
+// https://tools.ietf.org/html/rfc1977#section-1
+// https://github.com/vapier/ncompress/blob/main/compress.c
+
+const MAGIC: [u8; 2] = [0x1f, 0x9d];
+const MAX_BITS_MASK: u8 = 0x1f;
+const BLOCK_MODE: u8 = 0x80;
+const INIT_BITS: u32 = 9;
+// Empties the table, in block mode.
+const CLEAR: u32 = 256;
+
+// Decodes compressed data as it is written, collecting the output in a buffer.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    header: Vec<u8>,
+    max_bits: u32,
+    block_mode: bool,
+    // The width of the codes being read, and the largest entry the table can hold at that width.
+    width: u32,
+    max_code: u32,
+    // The next entry to be added to the table.
+    free: u32,
+    prefix: Vec<u16>,
+    suffix: Vec<u8>,
+    previous: Option<u32>,
+    last_char: u8,
+    // Input which has not yet been decoded, least significant bit first.
+    bits: u64,
+    bit_count: u32,
+    // How many bits have been read at the current width, and how many to throw away before the
+    // next code.
+    bits_at_width: u64,
+    skip: u64,
+    stack: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn output_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.output
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        for &byte in data {
+            if self.header.len() < 3 {
+                self.read_header(byte)?;
+                continue;
+            }
+            self.bits |= u64::from(byte) << self.bit_count;
+            self.bit_count += 8;
+            self.decode()?;
+        }
+        Ok(())
+    }
+
+    // Bits left over at the end are padding; only a missing header makes the data incomplete.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.header.len() < 3 {
+            return Err(invalid("truncated header"));
+        }
+        Ok(())
+    }
+
+    fn read_header(&mut self, byte: u8) -> io::Result<()> {
+        self.header.push(byte);
+        match self.header.len() {
+            1 | 2 if byte != MAGIC[self.header.len() - 1] => Err(invalid("not compressed data")),
+            3 => {
+                self.max_bits = u32::from(byte & MAX_BITS_MASK);
+                if !(INIT_BITS..=16).contains(&self.max_bits) {
+                    return Err(invalid("unsupported code width"));
+                }
+                self.block_mode = byte & BLOCK_MODE != 0;
+                self.prefix = vec![0; 1 << self.max_bits];
+                self.suffix = (0..1 << self.max_bits).map(|code| code as u8).collect();
+                self.reset_width();
+                self.free = if self.block_mode { CLEAR + 1 } else { CLEAR };
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn decode(&mut self) -> io::Result<()> {
+        loop {
+            if self.skip > 0 {
+                let n = self.skip.min(u64::from(self.bit_count));
+                self.consume(n as u32);
+                self.skip -= n;
+                if self.skip > 0 {
+                    return Ok(());
+                }
+            }
+            // Codes are written in groups of eight, so when the width changes the rest of the
+            // group at the old width is padding.
+            if self.free > self.max_code {
+                self.skip_to_group_end();
+                self.width += 1;
+                self.max_code = if self.width == self.max_bits {
+                    1 << self.max_bits
+                } else {
+                    (1 << self.width) - 1
+                };
+                continue;
+            }
+            if self.bit_count < self.width {
+                return Ok(());
+            }
+            let code = (self.bits & ((1 << self.width) - 1)) as u32;
+            self.consume(self.width);
+            self.bits_at_width += u64::from(self.width);
+
+            if code == CLEAR && self.block_mode {
+                self.skip_to_group_end();
+                self.reset_width();
+                self.free = CLEAR;
+                continue;
+            }
+            self.decode_code(code)?;
+        }
+    }
+
+    fn decode_code(&mut self, code: u32) -> io::Result<()> {
+        let previous = match self.previous {
+            Some(previous) => previous,
+            None => {
+                if code >= CLEAR {
+                    return Err(invalid("invalid first code"));
+                }
+                self.previous = Some(code);
+                self.last_char = code as u8;
+                self.output.push(self.last_char);
+                return Ok(());
+            }
+        };
+
+        // A code may refer to the entry which is about to be added, which is the previous string
+        // followed by its own first character.
+        let mut entry = code;
+        self.stack.clear();
+        if code >= self.free {
+            if code > self.free {
+                return Err(invalid("invalid code"));
+            }
+            self.stack.push(self.last_char);
+            entry = previous;
+        }
+        while entry >= CLEAR {
+            self.stack.push(self.suffix[entry as usize]);
+            entry = u32::from(self.prefix[entry as usize]);
+        }
+        self.last_char = self.suffix[entry as usize];
+        self.stack.push(self.last_char);
+        self.output.extend(self.stack.iter().rev());
+
+        if self.free < 1 << self.max_bits {
+            self.prefix[self.free as usize] = previous as u16;
+            self.suffix[self.free as usize] = self.last_char;
+            self.free += 1;
+        }
+        self.previous = Some(code);
+        Ok(())
+    }
+
+    fn reset_width(&mut self) {
+        self.width = INIT_BITS;
+        self.max_code = (1 << INIT_BITS) - 1;
+    }
+
+    fn skip_to_group_end(&mut self) {
+        let group = u64::from(self.width) * 8;
+        self.skip = (group - self.bits_at_width % group) % group;
+        self.bits_at_width = 0;
+    }
+
+    fn consume(&mut self, n: u32) {
+        self.bits >>= n;
+        self.bit_count -= n;
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    // Writes codes the way compress does: in groups of eight at the same width, with the rest of
+    // a group padded out whenever the width changes.
+    struct CodeWriter {
+        output: Vec<u8>,
+        bits: u64,
+        bit_count: u32,
+        width: u32,
+        group: Vec<u32>,
+    }
+
+    impl CodeWriter {
+        fn write(&mut self, code: u32) {
+            self.group.push(code);
+            if self.group.len() == 8 {
+                self.flush_group(false);
+            }
+        }
+
+        fn flush_group(&mut self, pad: bool) {
+            let padding = if pad && !self.group.is_empty() {
+                (8 - self.group.len() as u32) * self.width
+            } else {
+                0
+            };
+            for code in std::mem::take(&mut self.group) {
+                self.bits |= u64::from(code) << self.bit_count;
+                self.bit_count += self.width;
+                self.flush_bytes();
+            }
+            self.bit_count += padding;
+            self.flush_bytes();
+        }
+
+        fn flush_bytes(&mut self) {
+            while self.bit_count >= 8 {
+                self.output.push(self.bits as u8);
+                self.bits >>= 8;
+                self.bit_count -= 8;
+            }
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            self.flush_group(false);
+            if self.bit_count > 0 {
+                self.output.push(self.bits as u8);
+            }
+            self.output
+        }
+    }
+
+    // A compress compatible encoder which, like compress, empties its table with CLEAR as soon
+    // as it fills up.
+    fn compress(data: &[u8], max_bits: u32, block_mode: bool) -> Vec<u8> {
+        let mut header = max_bits as u8;
+        if block_mode {
+            header |= BLOCK_MODE;
+        }
+        let mut writer = CodeWriter {
+            output: vec![MAGIC[0], MAGIC[1], header],
+            bits: 0,
+            bit_count: 0,
+            width: INIT_BITS,
+            group: Vec::new(),
+        };
+        let first_free = if block_mode { CLEAR + 1 } else { CLEAR };
+        let mut free = first_free;
+        let mut max_code = (1 << INIT_BITS) - 1;
+        let mut table = HashMap::<(u32, u8), u32>::new();
+
+        let mut output = |writer: &mut CodeWriter, code: u32, free: u32, clear: bool| {
+            writer.write(code);
+            if clear {
+                writer.flush_group(true);
+                writer.width = INIT_BITS;
+                max_code = (1 << INIT_BITS) - 1;
+            } else if free > max_code {
+                writer.flush_group(true);
+                writer.width += 1;
+                max_code = if writer.width == max_bits {
+                    1 << max_bits
+                } else {
+                    (1 << writer.width) - 1
+                };
+            }
+        };
+
+        let (&first, rest) = match data.split_first() {
+            Some(split) => split,
+            None => return writer.finish(),
+        };
+        let mut entry = u32::from(first);
+        for &c in rest {
+            if let Some(&code) = table.get(&(entry, c)) {
+                entry = code;
+                continue;
+            }
+            output(&mut writer, entry, free, false);
+            if free < 1 << max_bits {
+                table.insert((entry, c), free);
+                free += 1;
+            } else if block_mode {
+                table.clear();
+                free = first_free;
+                output(&mut writer, CLEAR, free, true);
+            }
+            entry = u32::from(c);
+        }
+        output(&mut writer, entry, free, false);
+        writer.finish()
+    }
+
+    fn decompress(data: &[u8], chunk_size: usize) -> io::Result<Vec<u8>> {
+        let mut decoder = Decoder::new();
+        for chunk in data.chunks(chunk_size) {
+            decoder.write(chunk)?;
+        }
+        decoder.finish()?;
+        Ok(std::mem::take(decoder.output_mut()))
+    }
+
+    // Text with enough repetition to exercise long table entries, but enough variety to fill the
+    // table.
+    fn sample(len: usize) -> Vec<u8> {
+        let mut state = 1u32;
+        (0..len)
+            .map(|i| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                if i % 7 == 0 {
+                    b"the quick brown fox "[(state >> 16) as usize % 20]
+                } else {
+                    b'a' + ((state >> 16) % 16) as u8
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decodes_codes_as_written_by_compress() {
+        // Nothing repeats, so every character is a code of its own.
+        assert_eq!(
+            decompress(&[0x1f, 0x9d, 0x90, 0x61, 0xc4, 0x8c, 0x01], 1).unwrap(),
+            b"abc"
+        );
+        assert_eq!(decompress(&[0x1f, 0x9d, 0x90], 1).unwrap(), b"");
+    }
+
+    #[test]
+    fn decodes_code_for_entry_being_added() {
+        // "aaaa" is 'a', then code 257 for "aa" before the decoder has added it, then 'a'.
+        let data = [0x1f, 0x9d, 0x90, 0x61, 0x02, 0x86, 0x01];
+        assert_eq!(compress(b"aaaa", 16, true), data);
+        assert_eq!(decompress(&data, 1).unwrap(), b"aaaa");
+    }
+
+    #[test]
+    fn follows_code_width_growth() {
+        // Enough input to go through every width up to 16 bits.
+        let data = sample(100_000);
+        let compressed = compress(&data, 16, true);
+        assert_eq!(decompress(&compressed, 4096).unwrap(), data);
+        assert_eq!(decompress(&compressed, 3).unwrap(), data);
+    }
+
+    #[test]
+    fn starts_again_after_clear() {
+        // At 9 bits the table fills up, and is cleared, every few hundred codes.
+        let data = sample(50_000);
+        for max_bits in [9, 12] {
+            let compressed = compress(&data, max_bits, true);
+            assert_eq!(
+                decompress(&compressed, 1000).unwrap(),
+                data,
+                "{} bits",
+                max_bits
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_full_table_without_block_mode() {
+        let data = sample(20_000);
+        let compressed = compress(&data, 9, false);
+        assert_eq!(decompress(&compressed, 1000).unwrap(), data);
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        assert!(decompress(&[0x1f, 0x8b, 0x08], 1).is_err());
+        assert!(decompress(&[0x1f, 0x9d], 1).is_err());
+        assert!(decompress(&[0x1f, 0x9d, 0x91], 1).is_err());
+        // The second code is ahead of the table.
+        assert!(decompress(&[0x1f, 0x9d, 0x90, 0x61, 0x04, 0x86, 0x01], 1).is_err());
+    }
+}
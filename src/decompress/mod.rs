@@ -0,0 +1,115 @@
+use std::io::{self, Write};
+
+use flate2::write::GzDecoder;
+use flate2::{Decompress, FlushDecompress, Status};
+
+use crate::printer::Compression;
+
+mod lzw;
+
+// Decompresses a document as it arrives. Compressed data is written in, and the decompressed data
+// collects in a buffer for the caller to take.
+// https://tools.ietf.org/html/rfc8011#section-5.4.32
+pub enum Decoder {
+    None(Vec<u8>),
+    // https://tools.ietf.org/html/rfc1951
+    Deflate(Inflater),
+    // https://tools.ietf.org/html/rfc1952
+    GZip(GzDecoder<Vec<u8>>),
+    Compress(lzw::Decoder),
+}
+
+impl Decoder {
+    pub fn new(compression: Compression) -> Self {
+        match compression {
+            Compression::None => Decoder::None(Vec::new()),
+            Compression::Deflate => Decoder::Deflate(Inflater::new()),
+            Compression::GZip => Decoder::GZip(GzDecoder::new(Vec::new())),
+            Compression::Compress => Decoder::Compress(lzw::Decoder::new()),
+        }
+    }
+
+    // Fails if the data is not valid for the compression in use.
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        match self {
+            Decoder::None(output) => {
+                output.extend_from_slice(data);
+                Ok(())
+            }
+            Decoder::Deflate(inflater) => inflater.write(data),
+            Decoder::GZip(decoder) => decoder.write_all(data),
+            Decoder::Compress(decoder) => decoder.write(data),
+        }
+    }
+
+    // Decodes whatever is left once all of the data has been written. Fails if the data ended
+    // part way through.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self {
+            Decoder::None(_) => Ok(()),
+            Decoder::Deflate(inflater) => inflater.finish(),
+            Decoder::GZip(decoder) => decoder.try_finish(),
+            Decoder::Compress(decoder) => decoder.finish(),
+        }
+    }
+
+    // Takes the data decompressed so far.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        let output = match self {
+            Decoder::None(output) => output,
+            Decoder::Deflate(inflater) => &mut inflater.output,
+            Decoder::GZip(decoder) => decoder.get_mut(),
+            Decoder::Compress(decoder) => decoder.output_mut(),
+        };
+        std::mem::take(output)
+    }
+}
+
+// Raw deflate data has no trailer to check, so the decoder itself has to tell whether the data
+// reached the end of its final block.
+pub struct Inflater {
+    decompress: Decompress,
+    output: Vec<u8>,
+    ended: bool,
+}
+
+impl Inflater {
+    fn new() -> Self {
+        Self {
+            decompress: Decompress::new(false),
+            output: Vec::new(),
+            ended: false,
+        }
+    }
+
+    fn write(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            if self.ended {
+                return Err(invalid("data after the end of the compressed stream"));
+            }
+            self.output.reserve(INFLATE_BUFFER_SIZE);
+            let total_in = self.decompress.total_in();
+            let status = self
+                .decompress
+                .decompress_vec(data, &mut self.output, FlushDecompress::None)
+                .map_err(|e| invalid(&e.to_string()))?;
+            data = &data[(self.decompress.total_in() - total_in) as usize..];
+            self.ended = status == Status::StreamEnd;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if !self.ended {
+            return Err(invalid("compressed stream ended early"));
+        }
+        Ok(())
+    }
+}
+
+// How much room is made for decompressed data at a time.
+const INFLATE_BUFFER_SIZE: usize = 32 * 1024;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
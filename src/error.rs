@@ -0,0 +1,86 @@
+use std::fmt;
+
+use ipp::proto::model::StatusCode;
+
+// Errors which prevent a request from being serviced. Errors raised before the IPP header has been
+// read can only be reported at the HTTP level; everything else is reported to the client as an
+// IPP response with the corresponding status code.
+#[derive(Debug)]
+pub enum ServerError {
+    // The request body is not application/ipp.
+    UnsupportedMediaType(String),
+    // The request body could not be read or decoded as an IPP request.
+    MalformedRequest(String),
+    // The request asks for something other than 100-continue before sending its body.
+    // https://tools.ietf.org/html/rfc7231#section-5.1.1
+    ExpectationFailed(String),
+    // The Content-Length of the request is more than the printer accepts.
+    RequestTooLarge(u64),
+    // https://tools.ietf.org/html/rfc8011#section-4.1.8
+    VersionNotSupported(u16),
+    // The operation-id is unknown or not implemented by this printer.
+    OperationNotSupported(u16),
+    // The request payload could not be read.
+    Io(std::io::Error),
+}
+
+impl ServerError {
+    // https://tools.ietf.org/html/rfc8011#section-4.1.6.1
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ServerError::UnsupportedMediaType(_) => StatusCode::ClientErrorBadRequest,
+            ServerError::MalformedRequest(_) => StatusCode::ClientErrorBadRequest,
+            ServerError::ExpectationFailed(_) => StatusCode::ClientErrorBadRequest,
+            ServerError::RequestTooLarge(_) => StatusCode::ClientErrorRequestEntityTooLong,
+            ServerError::VersionNotSupported(_) => StatusCode::ServerErrorVersionNotSupported,
+            ServerError::OperationNotSupported(_) => StatusCode::ServerErrorOperationNotSupported,
+            ServerError::Io(_) => StatusCode::ServerErrorInternalError,
+        }
+    }
+
+    // The HTTP status to use when no IPP response can be produced.
+    // https://tools.ietf.org/html/rfc8010#section-4.2
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ServerError::UnsupportedMediaType(_) => 415,
+            ServerError::MalformedRequest(_) => 400,
+            ServerError::ExpectationFailed(_) => 417,
+            ServerError::RequestTooLarge(_) => 413,
+            ServerError::VersionNotSupported(_) => 400,
+            ServerError::OperationNotSupported(_) => 400,
+            ServerError::Io(_) => 500,
+        }
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::UnsupportedMediaType(content_type) => {
+                write!(f, "Unsupported content type: {}", content_type)
+            }
+            ServerError::MalformedRequest(reason) => write!(f, "Malformed request: {}", reason),
+            ServerError::ExpectationFailed(expect) => {
+                write!(f, "Unsupported expectation: {}", expect)
+            }
+            ServerError::RequestTooLarge(limit) => {
+                write!(f, "Request is larger than {} bytes", limit)
+            }
+            ServerError::VersionNotSupported(version) => {
+                write!(f, "Unsupported IPP version: {:#06x}", version)
+            }
+            ServerError::OperationNotSupported(operation) => {
+                write!(f, "Unsupported operation: {:#06x}", operation)
+            }
+            ServerError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+impl From<std::io::Error> for ServerError {
+    fn from(e: std::io::Error) -> Self {
+        ServerError::Io(e)
+    }
+}
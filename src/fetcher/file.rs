@@ -0,0 +1,165 @@
+use std::path::{Path, PathBuf};
+
+use futures::future::BoxFuture;
+use futures::{FutureExt, TryFutureExt};
+
+use crate::fetcher::{Document, FetchError, Fetcher};
+
+// Fetches file:// URIs. Only files beneath the root directory may be fetched, so clients cannot
+// read arbitrary files from the server.
+// https://tools.ietf.org/html/rfc8089
+#[derive(Debug)]
+pub struct FileFetcher {
+    root: PathBuf,
+}
+
+impl FileFetcher {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    async fn open(&self, uri: &str, max_size: u64) -> Result<tokio::fs::File, FetchError> {
+        let path = file_path(uri)
+            .ok_or_else(|| FetchError::AccessError(format!("Invalid file URI {}", uri)))?;
+
+        let access_error = |e: std::io::Error| FetchError::AccessError(e.to_string());
+        let root = tokio::fs::canonicalize(&self.root)
+            .await
+            .map_err(access_error)?;
+        let path = tokio::fs::canonicalize(&path).await.map_err(access_error)?;
+        if !path.starts_with(&root) {
+            return Err(FetchError::AccessError(format!(
+                "{} is outside {}",
+                path.display(),
+                root.display()
+            )));
+        }
+
+        let file = tokio::fs::File::open(&path).await.map_err(access_error)?;
+        let metadata = file.metadata().await.map_err(access_error)?;
+        if metadata.len() > max_size {
+            return Err(FetchError::TooLarge(max_size));
+        }
+        Ok(file)
+    }
+}
+
+impl Fetcher for FileFetcher {
+    fn schemes(&self) -> Vec<String> {
+        vec![String::from("file")]
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        uri: &'a str,
+        max_size: u64,
+    ) -> BoxFuture<'a, Result<Document, FetchError>> {
+        self.open(uri, max_size)
+            .map_ok(|file| Box::new(file) as Document)
+            .boxed()
+    }
+}
+
+// Accepts "file:/path", "file:///path" and "file://localhost/path". Files on other hosts cannot be
+// fetched.
+fn file_path(uri: &str) -> Option<PathBuf> {
+    let (_, rest) = uri.split_once(':')?;
+    let path = match rest.strip_prefix("//") {
+        Some(rest) => {
+            let (host, path) = rest.split_at(rest.find('/')?);
+            if !host.is_empty() && !host.eq_ignore_ascii_case("localhost") {
+                return None;
+            }
+            path
+        }
+        None => rest,
+    };
+    if !path.starts_with('/') {
+        return None;
+    }
+    percent_decode(path).map(PathBuf::from)
+}
+
+// https://tools.ietf.org/html/rfc3986#section-2.1
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::<u8>::new();
+    let mut chars = s.bytes();
+    while let Some(b) = chars.next() {
+        if b == b'%' {
+            let hex = [chars.next()?, chars.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    fn uri(path: &Path) -> String {
+        format!("file://{}", path.display())
+    }
+
+    #[tokio::test]
+    async fn fetches_files_beneath_root() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("document.txt");
+        std::fs::write(&path, b"hello\n").unwrap();
+        let fetcher = FileFetcher::new(root.path());
+        let mut data = Vec::<u8>::new();
+        let mut document = fetcher.fetch(&uri(&path), 100).await.unwrap();
+        document.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"hello\n");
+        assert!(matches!(
+            fetcher.fetch(&uri(&path), 5).await,
+            Err(FetchError::TooLarge(5))
+        ));
+    }
+
+    #[tokio::test]
+    async fn refuses_files_outside_root() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::NamedTempFile::new().unwrap();
+        let fetcher = FileFetcher::new(root.path());
+        assert!(matches!(
+            fetcher.fetch(&uri(outside.path()), 100).await,
+            Err(FetchError::AccessError(_))
+        ));
+
+        let escape = format!("{}/../{}", uri(root.path()), outside.path().display());
+        assert!(matches!(
+            fetcher.fetch(&escape, 100).await,
+            Err(FetchError::AccessError(_))
+        ));
+
+        let link = root.path().join("link");
+        std::os::unix::fs::symlink(outside.path(), &link).unwrap();
+        assert!(matches!(
+            fetcher.fetch(&uri(&link), 100).await,
+            Err(FetchError::AccessError(_))
+        ));
+    }
+
+    #[test]
+    fn parses_local_file_uris() {
+        assert_eq!(
+            file_path("file:///tmp/a%20b"),
+            Some(PathBuf::from("/tmp/a b"))
+        );
+        assert_eq!(file_path("file:/tmp/a"), Some(PathBuf::from("/tmp/a")));
+        assert_eq!(
+            file_path("file://localhost/tmp/a"),
+            Some(PathBuf::from("/tmp/a"))
+        );
+        assert_eq!(file_path("file://printer.example/tmp/a"), None);
+        assert_eq!(file_path("file:tmp/a"), None);
+    }
+}
@@ -0,0 +1,89 @@
+use std::io;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::{FutureExt, TryStreamExt};
+use hyper::client::HttpConnector;
+use hyper::header::CONTENT_LENGTH;
+use hyper::{Body, Client, Uri};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+
+use crate::fetcher::{Document, FetchError, Fetcher};
+use crate::timeout::IdleTimeout;
+
+// How long to wait for the server to respond, or to send more of the document, before giving up.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Fetches http:// and https:// URIs, or only those of the two it is given. Redirects are not
+// followed.
+#[derive(Debug)]
+pub struct HttpFetcher {
+    client: Client<HttpsConnector<HttpConnector>, Body>,
+    schemes: Vec<String>,
+}
+
+impl HttpFetcher {
+    pub fn new(schemes: Vec<String>) -> Self {
+        let connector = HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Self {
+            client: Client::builder().build(connector),
+            schemes,
+        }
+    }
+
+    async fn get(&self, uri: &str, max_size: u64) -> Result<Document, FetchError> {
+        let uri = uri
+            .parse::<Uri>()
+            .map_err(|e| FetchError::AccessError(e.to_string()))?;
+        let access_error = |e: hyper::Error| FetchError::AccessError(e.to_string());
+
+        let resp = self.client.get(uri).await.map_err(access_error)?;
+        if !resp.status().is_success() {
+            return Err(FetchError::AccessError(format!(
+                "Server responded {}",
+                resp.status()
+            )));
+        }
+
+        let content_length = resp
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok())
+            .and_then(|len| len.parse::<u64>().ok());
+        if content_length.unwrap_or_default() > max_size {
+            return Err(FetchError::TooLarge(max_size));
+        }
+
+        let body = resp
+            .into_body()
+            .map_err(io::Error::other)
+            .into_async_read()
+            .compat();
+        Ok(Box::new(IdleTimeout::new(body, FETCH_TIMEOUT)))
+    }
+}
+
+impl Fetcher for HttpFetcher {
+    fn schemes(&self) -> Vec<String> {
+        self.schemes.clone()
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        uri: &'a str,
+        max_size: u64,
+    ) -> BoxFuture<'a, Result<Document, FetchError>> {
+        async move {
+            match tokio::time::timeout(FETCH_TIMEOUT, self.get(uri, max_size)).await {
+                Ok(result) => result,
+                Err(_) => Err(FetchError::AccessError(String::from("Timed out"))),
+            }
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+
+use futures::future::BoxFuture;
+use tokio::io::AsyncRead;
+
+pub use crate::fetcher::file::FileFetcher;
+pub use crate::fetcher::http::HttpFetcher;
+
+mod file;
+mod http;
+
+#[derive(Debug)]
+pub enum FetchError {
+    // No fetcher is registered for the URI's scheme.
+    UnsupportedScheme(String),
+    // The document is larger than the printer is willing to accept.
+    TooLarge(u64),
+    // The document could not be retrieved.
+    AccessError(String),
+}
+
+// The data of a fetched document, read as it arrives rather than held in memory.
+pub type Document = Box<dyn AsyncRead + Send + Unpin>;
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::UnsupportedScheme(scheme) => {
+                write!(f, "Unsupported URI scheme: {}", scheme)
+            }
+            FetchError::TooLarge(max_size) => {
+                write!(f, "Document is larger than {} bytes", max_size)
+            }
+            FetchError::AccessError(reason) => write!(f, "Could not fetch document: {}", reason),
+        }
+    }
+}
+
+// Retrieves documents supplied by reference in Print-URI and Send-URI requests.
+// https://tools.ietf.org/html/rfc8011#section-4.2.2
+pub trait Fetcher: fmt::Debug + Send + Sync {
+    // The URI schemes handled by this fetcher, in lower case.
+    fn schemes(&self) -> Vec<String>;
+
+    // Opens the document for reading, failing with FetchError::TooLarge if it is known up front to
+    // be larger than max_size bytes. The reader is not limited; the spool stops reading at
+    // max_size.
+    fn fetch<'a>(
+        &'a self,
+        uri: &'a str,
+        max_size: u64,
+    ) -> BoxFuture<'a, Result<Document, FetchError>>;
+}
+
+// The fetchers available to the printer, keyed by URI scheme.
+#[derive(Debug, Default)]
+pub struct Fetchers {
+    fetchers: Vec<Box<dyn Fetcher>>,
+}
+
+impl Fetchers {
+    // Adds a fetcher. It takes precedence over any fetcher registered earlier for the same scheme.
+    pub fn register(&mut self, fetcher: Box<dyn Fetcher>) {
+        self.fetchers.insert(0, fetcher);
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-5.4.27
+    pub fn schemes(&self) -> Vec<String> {
+        let mut schemes = Vec::<String>::new();
+        for fetcher in &self.fetchers {
+            for scheme in fetcher.schemes() {
+                if !schemes.contains(&scheme) {
+                    schemes.push(scheme);
+                }
+            }
+        }
+        schemes.sort();
+        schemes
+    }
+
+    pub async fn fetch(&self, uri: &str, max_size: u64) -> Result<Document, FetchError> {
+        match self.fetcher_for(uri) {
+            Some(fetcher) => fetcher.fetch(uri, max_size).await,
+            None => Err(FetchError::UnsupportedScheme(
+                scheme(uri).unwrap_or_default(),
+            )),
+        }
+    }
+
+    fn fetcher_for(&self, uri: &str) -> Option<&dyn Fetcher> {
+        let scheme = scheme(uri)?;
+        self.fetchers
+            .iter()
+            .find(|fetcher| fetcher.schemes().contains(&scheme))
+            .map(|fetcher| fetcher.as_ref())
+    }
+}
+
+// Schemes are case-insensitive, so they are compared in lower case.
+// https://tools.ietf.org/html/rfc3986#section-3.1
+fn scheme(uri: &str) -> Option<String> {
+    let (scheme, _) = uri.split_once(':')?;
+    let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+    if valid {
+        Some(scheme.to_lowercase())
+    } else {
+        None
+    }
+}
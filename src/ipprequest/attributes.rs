@@ -0,0 +1,87 @@
+use ipp::proto::attribute::IppAttribute;
+use ipp::proto::model::DelimiterTag;
+use ipp::proto::request::IppRequestResponse;
+use ipp::proto::value::IppValue;
+use log::warn;
+
+pub fn operation_attribute<'a>(req: &'a IppRequestResponse, name: &str) -> Option<&'a IppValue> {
+    req.attributes()
+        .groups_of(DelimiterTag::OperationAttributes)
+        .find_map(|group| group.attributes().get(name))
+        .map(|attr| attr.value())
+}
+
+// Job Template attributes are supplied in the Job Attributes group.
+// https://tools.ietf.org/html/rfc8011#section-5.2
+pub fn job_attribute<'a>(req: &'a IppRequestResponse, name: &str) -> Option<&'a IppValue> {
+    req.attributes()
+        .groups_of(DelimiterTag::JobAttributes)
+        .find_map(|group| group.attributes().get(name))
+        .map(|attr| attr.value())
+}
+
+// Jobs are targeted either by "printer-uri" plus "job-id" or by "job-uri" alone.
+// https://tools.ietf.org/html/rfc8011#section-4.3
+pub fn job_id(req: &IppRequestResponse) -> Option<u32> {
+    if let Some(IppValue::Integer(id)) = operation_attribute(req, "job-id") {
+        return if *id > 0 { Some(*id as u32) } else { None };
+    }
+
+    match operation_attribute(req, "job-uri") {
+        Some(IppValue::Uri(uri)) => uri.rsplit('/').next().and_then(|id| id.parse().ok()),
+        _ => None,
+    }
+}
+
+// Returns the keywords listed in "requested-attributes", or None if the client did not supply any.
+// https://tools.ietf.org/html/rfc8011#section-4.2.5.1
+pub fn requested_attributes(req: &IppRequestResponse) -> Option<Vec<String>> {
+    let value = operation_attribute(req, "requested-attributes")?;
+    let values = match value {
+        IppValue::Array(values) => values.iter().collect(),
+        value => vec![value],
+    };
+
+    let mut keywords = Vec::<String>::new();
+    for keyword_value in values {
+        match keyword_value {
+            IppValue::Keyword(keyword) => keywords.push(keyword.clone()),
+            _ => warn!(
+                "Found unexpected value type in requested-attributes: {:?}",
+                keyword_value
+            ),
+        }
+    }
+    Some(keywords)
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.1.6
+pub fn requesting_user_name(req: &IppRequestResponse) -> Option<String> {
+    match operation_attribute(req, "requesting-user-name") {
+        Some(IppValue::NameWithoutLanguage(name)) if !name.is_empty() => Some(name.clone()),
+        _ => None,
+    }
+}
+
+// An authenticated request is made by the user it was authenticated as, whatever name the client
+// supplied, so that job-originating-user-name is the most authenticated name available.
+// https://tools.ietf.org/html/rfc8011#section-5.3.6
+pub fn set_requesting_user_name(req: &mut IppRequestResponse, user: &str) {
+    req.attributes_mut().add(
+        DelimiterTag::OperationAttributes,
+        IppAttribute::new(
+            "requesting-user-name",
+            IppValue::NameWithoutLanguage(String::from(user)),
+        ),
+    );
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.2.1.1
+pub fn job_name(req: &IppRequestResponse) -> Option<String> {
+    let value =
+        operation_attribute(req, "job-name").or_else(|| operation_attribute(req, "document-name"));
+    match value {
+        Some(IppValue::NameWithoutLanguage(name)) if !name.is_empty() => Some(name.clone()),
+        _ => None,
+    }
+}
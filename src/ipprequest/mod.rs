@@ -0,0 +1,13 @@
+pub use attributes::{
+    job_attribute, job_id, job_name, operation_attribute, requested_attributes,
+    requesting_user_name, set_requesting_user_name,
+};
+pub use parser::{parse, ParseError, ParseErrorKind};
+pub use validation::{
+    compression, document_format, job_hold_until, job_hold_until_time, validate_job_creation,
+    validate_operation_attributes, ValidationError,
+};
+
+mod attributes;
+mod parser;
+mod validation;
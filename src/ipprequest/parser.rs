@@ -0,0 +1,154 @@
+use futures::io::AsyncRead;
+use ipp::proto::attribute::{IppAttribute, IppAttributeGroup};
+use ipp::proto::model::{DelimiterTag, Operation, ValueTag};
+use ipp::proto::parser::IppParseError;
+use ipp::proto::reader::IppReader;
+use ipp::proto::request::IppRequestResponse;
+use ipp::proto::value::IppValue;
+use num_traits::FromPrimitive;
+
+// The octets taken up by the header of a request.
+// https://tools.ietf.org/html/rfc8010#section-3.1.1
+const HEADER_SIZE: u64 = 8;
+
+// A request which could not be parsed. If the header was read, the failure can still be reported
+// in an IPP response to the request.
+#[derive(Debug)]
+pub struct ParseError {
+    pub request_id: Option<u32>,
+    pub error: ParseErrorKind,
+}
+
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    Invalid(IppParseError),
+    // The attributes went on past the given number of octets.
+    TooLarge(u64),
+}
+
+// Parses an IPP request, reading no further than the end of its attributes. The rest of the input
+// becomes the payload, so a document can be read from it as it arrives. The header and attributes
+// together may take up no more than max_size octets, as they are held in memory.
+//
+// This follows ipp::proto::parser::IppParser, which files the last attribute of each group under
+// the group that follows it. That moves e.g. "printer-uri" into the Job Attributes group whenever a
+// client sends Job Template attributes, so we use our own parser instead.
+// https://tools.ietf.org/html/rfc8010#section-3.1.1
+pub async fn parse<R>(inner: R, max_size: u64) -> Result<IppRequestResponse, ParseError>
+where
+    R: 'static + AsyncRead + Send + Sync + Unpin,
+{
+    let mut reader = IppReader::new(inner);
+    let header = reader.read_header().await.map_err(|e| ParseError {
+        request_id: None,
+        error: ParseErrorKind::Invalid(e.into()),
+    })?;
+    let groups = parse_attributes(&mut reader, max_size.saturating_sub(HEADER_SIZE))
+        .await
+        .map_err(|error| ParseError {
+            request_id: Some(header.request_id),
+            error,
+        })?;
+
+    // IppRequestResponse can only be constructed with a default set of attributes, which we replace.
+    let mut req = IppRequestResponse::new(header.version, Operation::PrintJob, None);
+    *req.header_mut() = header;
+    *req.attributes_mut().groups_mut() = groups;
+    *req.payload_mut() = reader.into_payload();
+    Ok(req)
+}
+
+// Each tag, name and value is read in full before its size is checked, so no more than one
+// attribute's worth past the limit is ever read.
+async fn parse_attributes<R>(
+    reader: &mut IppReader<R>,
+    max_size: u64,
+) -> Result<Vec<IppAttributeGroup>, ParseErrorKind>
+where
+    R: 'static + AsyncRead + Send + Sync + Unpin,
+{
+    let invalid = ParseErrorKind::Invalid;
+    let mut size = 0u64;
+    let mut groups = Vec::<IppAttributeGroup>::new();
+    let mut current_group: Option<IppAttributeGroup> = None;
+    let mut last_name: Option<String> = None;
+    // A stack of value lists. The bottom holds the values of the current attribute and each
+    // collection being parsed pushes another list.
+    let mut context: Vec<Vec<IppValue>> = vec![vec![]];
+
+    loop {
+        let tag = reader.read_tag().await.map_err(|e| invalid(e.into()))?;
+        size += 1;
+        match tag {
+            tag @ 0x01..=0x05 => {
+                let tag = DelimiterTag::from_u8(tag)
+                    .ok_or_else(|| invalid(IppParseError::InvalidTag(tag)))?;
+                add_attribute(&mut current_group, &mut last_name, &mut context);
+                if let Some(group) = current_group.take() {
+                    groups.push(group);
+                }
+                if tag == DelimiterTag::EndOfAttributes {
+                    break;
+                }
+                current_group = Some(IppAttributeGroup::new(tag));
+            }
+            tag @ 0x10..=0x4a => {
+                let name = reader.read_name().await.map_err(|e| invalid(e.into()))?;
+                let raw = reader.read_value().await.map_err(|e| invalid(e.into()))?;
+                // Each of the name and value is preceded by its length in two octets.
+                size += 4 + name.len() as u64 + raw.len() as u64;
+                if size > max_size {
+                    return Err(ParseErrorKind::TooLarge(max_size));
+                }
+                let value = IppValue::parse(tag, raw).map_err(|e| invalid(e.into()))?;
+
+                if !name.is_empty() {
+                    // Single attribute or the beginning of an array
+                    add_attribute(&mut current_group, &mut last_name, &mut context);
+                    last_name = Some(name);
+                }
+
+                if tag == ValueTag::BegCollection as u8 {
+                    context.push(vec![]);
+                } else if tag == ValueTag::EndCollection as u8 {
+                    let collection = context
+                        .pop()
+                        .ok_or(invalid(IppParseError::InvalidCollection))?;
+                    context
+                        .last_mut()
+                        .ok_or(invalid(IppParseError::InvalidCollection))?
+                        .push(IppValue::Collection(collection));
+                } else if let Some(values) = context.last_mut() {
+                    values.push(value);
+                }
+            }
+            tag => return Err(invalid(IppParseError::InvalidTag(tag))),
+        }
+    }
+    Ok(groups)
+}
+
+fn add_attribute(
+    group: &mut Option<IppAttributeGroup>,
+    last_name: &mut Option<String>,
+    context: &mut Vec<Vec<IppValue>>,
+) {
+    let name = match last_name.take() {
+        Some(name) => name,
+        None => return,
+    };
+    // Any collection left open is discarded along with its values.
+    context.truncate(1);
+    let mut values = context.pop().unwrap_or_default();
+    context.push(vec![]);
+
+    let value = if values.len() == 1 {
+        values.remove(0)
+    } else {
+        IppValue::Array(values)
+    };
+    if let Some(group) = group {
+        let attr = IppAttribute::new(&name, value);
+        group.attributes_mut().insert(name, attr);
+    }
+}
@@ -0,0 +1,288 @@
+use std::convert::TryFrom;
+use std::time::SystemTime;
+
+use chrono::{FixedOffset, TimeZone};
+
+use ipp::prelude::Uri;
+use ipp::proto::attribute::IppAttribute;
+use ipp::proto::model::{DelimiterTag, StatusCode, ValueTag};
+use ipp::proto::request::IppRequestResponse;
+use ipp::proto::value::IppValue;
+
+use crate::ipprequest::attributes::{job_attribute, operation_attribute};
+use crate::printer::{Charset, Compression, JobHoldUntil, MimeMediaType, Printer};
+
+// Operation attributes understood by the job creation operations.
+// https://tools.ietf.org/html/rfc8011#section-4.2.1.1
+const JOB_CREATION_OPERATION_ATTRIBUTES: [&str; 14] = [
+    "attributes-charset",
+    "attributes-natural-language",
+    "printer-uri",
+    "requesting-user-name",
+    "job-name",
+    "ipp-attribute-fidelity",
+    "document-name",
+    "document-uri",
+    "compression",
+    "document-format",
+    "document-natural-language",
+    "job-k-octets",
+    "job-impressions",
+    "job-media-sheets",
+];
+
+// Job Template attributes the printer supports.
+// https://tools.ietf.org/html/rfc8011#section-5.2
+const JOB_TEMPLATE_ATTRIBUTES: [&str; 2] = ["job-hold-until", "job-hold-until-time"];
+
+#[derive(Debug)]
+pub struct ValidationError {
+    pub status: StatusCode,
+    pub unsupported: Vec<IppAttribute>,
+}
+
+impl ValidationError {
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            status,
+            unsupported: Vec::new(),
+        }
+    }
+
+    fn unsupported(status: StatusCode, attr: IppAttribute) -> Self {
+        Self {
+            status,
+            unsupported: vec![attr],
+        }
+    }
+}
+
+// The out-of-band value reported for attributes the printer does not support at all.
+// https://tools.ietf.org/html/rfc8010#section-3.5.2
+fn unsupported_value() -> IppValue {
+    IppValue::Other {
+        tag: ValueTag::Unsupported as u8,
+        data: Default::default(),
+    }
+}
+
+// Checks the operation attributes every request must carry.
+// https://tools.ietf.org/html/rfc8011#section-4.1.4
+// https://tools.ietf.org/html/rfc8011#section-4.1.5
+pub fn validate_operation_attributes(
+    printer: &Printer,
+    req: &IppRequestResponse,
+) -> Result<(), ValidationError> {
+    match operation_attribute(req, "attributes-charset") {
+        Some(IppValue::Charset(charset)) => match Charset::try_from(charset.clone()) {
+            Ok(charset) if printer.charset_supported.contains(&charset) => {}
+            _ => {
+                return Err(ValidationError::unsupported(
+                    StatusCode::ClientErrorCharsetNotSupported,
+                    IppAttribute::new("attributes-charset", IppValue::Charset(charset.clone())),
+                ))
+            }
+        },
+        _ => return Err(ValidationError::new(StatusCode::ClientErrorBadRequest)),
+    }
+
+    // Any natural language is accepted. Generated text is always in natural-language-configured.
+    match operation_attribute(req, "attributes-natural-language") {
+        Some(IppValue::NaturalLanguage(_)) => {}
+        _ => return Err(ValidationError::new(StatusCode::ClientErrorBadRequest)),
+    }
+
+    let target = match operation_attribute(req, "printer-uri") {
+        Some(IppValue::Uri(uri)) => uri,
+        Some(_) => return Err(ValidationError::new(StatusCode::ClientErrorBadRequest)),
+        None => match operation_attribute(req, "job-uri") {
+            Some(IppValue::Uri(uri)) => uri,
+            _ => return Err(ValidationError::new(StatusCode::ClientErrorBadRequest)),
+        },
+    };
+    if !printer_serves_uri(printer, target) {
+        return Err(ValidationError::new(StatusCode::ClientErrorNotFound));
+    }
+
+    Ok(())
+}
+
+// Clients address the printer by whichever host name they know it by, so only the path is compared.
+fn printer_serves_uri(printer: &Printer, target: &str) -> bool {
+    let target_path = match target.parse::<Uri>() {
+        Ok(uri) => String::from(uri.path()),
+        Err(_) => return false,
+    };
+    printer
+        .printer_uri_supported
+        .iter()
+        .any(|supported| match supported.uri.parse::<Uri>() {
+            Ok(uri) => {
+                target_path == uri.path() || target_path.starts_with(&format!("{}/", uri.path()))
+            }
+            Err(_) => false,
+        })
+}
+
+// Returns the document-format supplied with a request, or the printer's default.
+// https://tools.ietf.org/html/rfc8011#section-4.2.1.1
+pub fn document_format(
+    printer: &Printer,
+    req: &IppRequestResponse,
+) -> Result<MimeMediaType, ValidationError> {
+    let value = match operation_attribute(req, "document-format") {
+        Some(IppValue::MimeMediaType(value)) => value,
+        Some(_) => return Err(ValidationError::new(StatusCode::ClientErrorBadRequest)),
+        None => return Ok(printer.document_format_default),
+    };
+    match MimeMediaType::try_from(value.as_str()) {
+        Ok(format) if printer.document_format_supported.contains(&format) => Ok(format),
+        _ => Err(ValidationError::unsupported(
+            StatusCode::ClientErrorDocumentFormatNotSupported,
+            IppAttribute::new("document-format", IppValue::MimeMediaType(value.clone())),
+        )),
+    }
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.2.1.1
+pub fn compression(
+    printer: &Printer,
+    req: &IppRequestResponse,
+) -> Result<Compression, ValidationError> {
+    let value = match operation_attribute(req, "compression") {
+        Some(IppValue::Keyword(value)) => value,
+        Some(_) => return Err(ValidationError::new(StatusCode::ClientErrorBadRequest)),
+        None => return Ok(Compression::None),
+    };
+    match Compression::try_from(value.as_str()) {
+        Ok(compression) if printer.compression_supported.contains(&compression) => Ok(compression),
+        _ => Err(ValidationError::unsupported(
+            StatusCode::ClientErrorCompressionNotSupported,
+            IppAttribute::new("compression", IppValue::Keyword(value.clone())),
+        )),
+    }
+}
+
+// Returns the job-hold-until value supplied by a client, or None if it was omitted. The value may
+// be supplied as a Job Template attribute or, for Hold-Job, as an operation attribute.
+// https://tools.ietf.org/html/rfc8011#section-5.2.2
+pub fn job_hold_until(
+    printer: &Printer,
+    value: Option<&IppValue>,
+) -> Result<Option<JobHoldUntil>, ValidationError> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let until = match value {
+        IppValue::Keyword(keyword) | IppValue::NameWithoutLanguage(keyword) => {
+            JobHoldUntil::try_from(keyword.as_str()).ok()
+        }
+        _ => None,
+    };
+    match until {
+        Some(until) if printer.job_hold_until_supported.contains(&until) => Ok(Some(until)),
+        _ => Err(ValidationError::unsupported(
+            StatusCode::ClientErrorAttributesOrValuesNotSupported,
+            IppAttribute::new("job-hold-until", value.clone()),
+        )),
+    }
+}
+
+// Returns the point in time a job-hold-until-time value refers to, or None if it was omitted.
+// https://ftp.pwg.org/pub/pwg/candidates/cs-ippjobext20-20190816-5100.7.pdf
+pub fn job_hold_until_time(
+    value: Option<&IppValue>,
+) -> Result<Option<SystemTime>, ValidationError> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let time = match *value {
+        IppValue::DateTime {
+            year,
+            month,
+            day,
+            hour,
+            minutes,
+            seconds,
+            utcdir,
+            utchours,
+            utcmins,
+            ..
+        } => {
+            let offset =
+                (utchours as i32 * 3600 + utcmins as i32 * 60) * if utcdir == '-' { -1 } else { 1 };
+            FixedOffset::east_opt(offset).and_then(|tz| {
+                tz.with_ymd_and_hms(
+                    year as i32,
+                    month as u32,
+                    day as u32,
+                    hour as u32,
+                    minutes as u32,
+                    seconds as u32,
+                )
+                .single()
+            })
+        }
+        _ => None,
+    };
+    match time {
+        Some(time) => Ok(Some(SystemTime::from(time))),
+        None => Err(ValidationError::unsupported(
+            StatusCode::ClientErrorAttributesOrValuesNotSupported,
+            IppAttribute::new("job-hold-until-time", value.clone()),
+        )),
+    }
+}
+
+// The validation shared by Print-Job, Validate-Job and Create-Job. On success, returns the
+// attributes which the printer will ignore. If the client asked for ipp-attribute-fidelity these
+// cause the request to be rejected instead.
+// https://tools.ietf.org/html/rfc8011#section-4.2.3
+pub fn validate_job_creation(
+    printer: &Printer,
+    req: &IppRequestResponse,
+) -> Result<Vec<IppAttribute>, ValidationError> {
+    validate_operation_attributes(printer, req)?;
+    document_format(printer, req)?;
+    compression(printer, req)?;
+
+    let mut ignored = Vec::<IppAttribute>::new();
+    for group in req.attributes().groups() {
+        for name in group.attributes().keys() {
+            let supported = match group.tag() {
+                DelimiterTag::OperationAttributes => {
+                    JOB_CREATION_OPERATION_ATTRIBUTES.contains(&name.as_str())
+                }
+                DelimiterTag::JobAttributes => JOB_TEMPLATE_ATTRIBUTES.contains(&name.as_str()),
+                _ => false,
+            };
+            if !supported {
+                ignored.push(IppAttribute::new(name, unsupported_value()));
+            }
+        }
+    }
+
+    // Supported Job Template attributes with unsupported values are ignored in the same way.
+    // https://tools.ietf.org/html/rfc8011#section-4.1.7
+    if let Err(e) = job_hold_until(printer, job_attribute(req, "job-hold-until")) {
+        ignored.extend(e.unsupported);
+    }
+    if let Err(e) = job_hold_until_time(job_attribute(req, "job-hold-until-time")) {
+        ignored.extend(e.unsupported);
+    }
+
+    let fidelity = matches!(
+        operation_attribute(req, "ipp-attribute-fidelity"),
+        Some(IppValue::Boolean(true))
+    );
+    if fidelity && !ignored.is_empty() {
+        return Err(ValidationError {
+            status: StatusCode::ClientErrorAttributesOrValuesNotSupported,
+            unsupported: ignored,
+        });
+    }
+
+    Ok(ignored)
+}
@@ -1,3 +1,6 @@
+use std::convert::TryFrom;
+
+#[allow(dead_code)]
 #[derive(Copy, Clone, Debug)]
 pub enum OperationAttributes {
     AttributesCharset,
@@ -6,7 +9,7 @@ pub enum OperationAttributes {
     DetailedStatusMessage,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum PrinterAttribute {
     // IPP/1.1 Attributes
     CharsetConfigured,
@@ -26,6 +29,7 @@ pub enum PrinterAttribute {
     PrinterUpTime,
     PrinterUriSupported,
     QueuedJobCount,
+    ReferenceUriSchemesSupported,
     UriAuthenticationSupported,
     UriSecuritySupported,
     // // IPP/2.0 Attributes
@@ -55,8 +59,8 @@ pub enum PrinterAttribute {
     // SidesSupported,
     // // IPP/2.1 Attributes
     // IppgetEventLife,
-    // JobHoldUntilDefault,
-    // JobHoldUntilSupported,
+    JobHoldUntilDefault,
+    JobHoldUntilSupported,
     // JobPriorityDefault,
     // JobPrioritySupported,
     // JobSettableAttributesSupported,
@@ -77,10 +81,10 @@ pub enum PrinterAttribute {
     // PrinterAlertDescription,
     // PrinterSettableAttributesSupported,
     // PrinterStateChangeTime,
-    // WhichJobsSupported,
+    WhichJobsSupported,
     // // IPP/2.2 Attributes
     // JobCreationAttributesSupported,
-    // JobIdsSupported,
+    JobIdsSupported,
     // MultipleDocumentJobsSupported,
     // NumberUpDefault,
     // NumberUpSupported,
@@ -89,24 +93,273 @@ pub enum PrinterAttribute {
     // PrinterMessageFromOperator,
 }
 
+impl PrinterAttribute {
+    // https://tools.ietf.org/html/rfc8011#section-5.4
+    pub fn description() -> Vec<PrinterAttribute> {
+        vec![
+            // IPP/1.1 Attributes
+            PrinterAttribute::CharsetConfigured,
+            PrinterAttribute::CharsetSupported,
+            PrinterAttribute::CompressionSupported,
+            PrinterAttribute::DocumentFormatDefault,
+            PrinterAttribute::DocumentFormatSupported,
+            PrinterAttribute::GeneratedNaturalLanguageSupported,
+            PrinterAttribute::IppVersionsSupported,
+            PrinterAttribute::NaturalLanguageConfigured,
+            PrinterAttribute::OperationsSupported,
+            PrinterAttribute::PdlOverrideSupported,
+            PrinterAttribute::PrinterIsAcceptingJobs,
+            PrinterAttribute::PrinterName,
+            PrinterAttribute::PrinterState,
+            PrinterAttribute::PrinterStateReasons,
+            PrinterAttribute::PrinterUpTime,
+            PrinterAttribute::PrinterUriSupported,
+            PrinterAttribute::QueuedJobCount,
+            PrinterAttribute::ReferenceUriSchemesSupported,
+            PrinterAttribute::UriAuthenticationSupported,
+            PrinterAttribute::UriSecuritySupported,
+            // IPP/2.1 Attributes
+            PrinterAttribute::WhichJobsSupported,
+            // IPP/2.2 Attributes
+            PrinterAttribute::JobIdsSupported,
+        ]
+    }
+
+    // The "xxx-default" and "xxx-supported" attributes for each Job Template attribute.
+    // https://tools.ietf.org/html/rfc8011#section-5.2
+    pub fn template() -> Vec<PrinterAttribute> {
+        vec![
+            PrinterAttribute::JobHoldUntilDefault,
+            PrinterAttribute::JobHoldUntilSupported,
+        ]
+    }
+
+    // Expands a requested-attributes keyword, which may name a group of attributes, into the
+    // attributes it selects.
+    // https://tools.ietf.org/html/rfc8011#section-4.2.5.1
+    pub fn expand(keyword: &str) -> Result<Vec<PrinterAttribute>, String> {
+        match keyword {
+            "all" => {
+                let mut attrs = PrinterAttribute::template();
+                attrs.append(&mut PrinterAttribute::description());
+                Ok(attrs)
+            }
+            "printer-description" => Ok(PrinterAttribute::description()),
+            "job-template" => Ok(PrinterAttribute::template()),
+            keyword => PrinterAttribute::try_from(keyword).map(|attr| vec![attr]),
+        }
+    }
+
+    // Resolves a list of requested-attributes keywords. Returns the selected attributes, without
+    // duplicates, along with any keywords that were not recognised.
+    pub fn from_keywords(keywords: &[String]) -> (Vec<PrinterAttribute>, Vec<String>) {
+        let mut attrs = Vec::<PrinterAttribute>::new();
+        let mut unknown = Vec::<String>::new();
+        for keyword in keywords {
+            match PrinterAttribute::expand(keyword) {
+                Ok(expanded) => {
+                    for attr in expanded {
+                        if !attrs.contains(&attr) {
+                            attrs.push(attr);
+                        }
+                    }
+                }
+                Err(_) => unknown.push(keyword.clone()),
+            }
+        }
+        (attrs, unknown)
+    }
+}
+
+impl From<PrinterAttribute> for String {
+    fn from(a: PrinterAttribute) -> Self {
+        match a {
+            PrinterAttribute::CharsetConfigured => String::from("charset-configured"),
+            PrinterAttribute::CharsetSupported => String::from("charset-supported"),
+            PrinterAttribute::CompressionSupported => String::from("compression-supported"),
+            PrinterAttribute::DocumentFormatDefault => String::from("document-format-default"),
+            PrinterAttribute::DocumentFormatSupported => String::from("document-format-supported"),
+            PrinterAttribute::GeneratedNaturalLanguageSupported => {
+                String::from("generated-natural-language-supported")
+            }
+            PrinterAttribute::IppVersionsSupported => String::from("ipp-versions-supported"),
+            PrinterAttribute::NaturalLanguageConfigured => {
+                String::from("natural-language-configured")
+            }
+            PrinterAttribute::OperationsSupported => String::from("operations-supported"),
+            PrinterAttribute::PdlOverrideSupported => String::from("pdl-override-supported"),
+            PrinterAttribute::PrinterIsAcceptingJobs => String::from("printer-is-accepting-jobs"),
+            PrinterAttribute::PrinterName => String::from("printer-name"),
+            PrinterAttribute::PrinterState => String::from("printer-state"),
+            PrinterAttribute::PrinterStateReasons => String::from("printer-state-reasons"),
+            PrinterAttribute::PrinterUpTime => String::from("printer-up-time"),
+            PrinterAttribute::PrinterUriSupported => String::from("printer-uri-supported"),
+            PrinterAttribute::QueuedJobCount => String::from("queued-job-count"),
+            PrinterAttribute::ReferenceUriSchemesSupported => {
+                String::from("reference-uri-schemes-supported")
+            }
+            PrinterAttribute::UriAuthenticationSupported => {
+                String::from("uri-authentication-supported")
+            }
+            PrinterAttribute::UriSecuritySupported => String::from("uri-security-supported"),
+            PrinterAttribute::JobHoldUntilDefault => String::from("job-hold-until-default"),
+            PrinterAttribute::JobHoldUntilSupported => String::from("job-hold-until-supported"),
+            PrinterAttribute::WhichJobsSupported => String::from("which-jobs-supported"),
+            PrinterAttribute::JobIdsSupported => String::from("job-ids-supported"),
+        }
+    }
+}
+
+impl TryFrom<&str> for PrinterAttribute {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        PrinterAttribute::description()
+            .into_iter()
+            .chain(PrinterAttribute::template())
+            .find(|&attr| String::from(attr) == value)
+            .ok_or_else(|| format!("Unknown Printer Attribute {}", value))
+    }
+}
+
 //https://tools.ietf.org/html/rfc8011#section-5.3
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum JobAttribute {
     // IPP/1.1 Attributes
+    AttributesCharset,
+    AttributesNaturalLanguage,
     JobId,
-    JobUri,
+    JobKOctets,
+    JobName,
+    JobOriginatingUserName,
+    JobPrinterUpTime,
+    JobPrinterUri,
     JobState,
     JobStateReasons,
-    // TODO: Add remaining job attributes
+    JobUri,
+    NumberOfDocuments,
+    NumberOfInterveningJobs,
+    TimeAtCompleted,
+    TimeAtCreation,
+    TimeAtProcessing,
+    // IPP/2.0 Attributes
+    DateTimeAtCompleted,
+    DateTimeAtCreation,
+    DateTimeAtProcessing,
+    // IPP Job Extensions v2.0 Attributes
+    // https://ftp.pwg.org/pub/pwg/candidates/cs-ippjobext20-20190816-5100.7.pdf
+    DocumentFormatDetected,
+    // Job Template Attributes
+    JobHoldUntil,
+    JobHoldUntilTime,
+}
+
+impl JobAttribute {
+    // https://tools.ietf.org/html/rfc8011#section-5.3
+    pub fn description() -> Vec<JobAttribute> {
+        vec![
+            JobAttribute::AttributesCharset,
+            JobAttribute::AttributesNaturalLanguage,
+            JobAttribute::JobId,
+            JobAttribute::JobKOctets,
+            JobAttribute::JobName,
+            JobAttribute::JobOriginatingUserName,
+            JobAttribute::JobPrinterUpTime,
+            JobAttribute::JobPrinterUri,
+            JobAttribute::JobState,
+            JobAttribute::JobStateReasons,
+            JobAttribute::JobUri,
+            JobAttribute::NumberOfDocuments,
+            JobAttribute::NumberOfInterveningJobs,
+            JobAttribute::TimeAtCompleted,
+            JobAttribute::TimeAtCreation,
+            JobAttribute::TimeAtProcessing,
+            JobAttribute::DateTimeAtCompleted,
+            JobAttribute::DateTimeAtCreation,
+            JobAttribute::DateTimeAtProcessing,
+            JobAttribute::DocumentFormatDetected,
+        ]
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-5.2
+    pub fn template() -> Vec<JobAttribute> {
+        vec![JobAttribute::JobHoldUntil, JobAttribute::JobHoldUntilTime]
+    }
+
+    // Expands a requested-attributes keyword, which may name a group of attributes, into the
+    // attributes it selects.
+    // https://tools.ietf.org/html/rfc8011#section-4.3.4.1
+    pub fn expand(keyword: &str) -> Result<Vec<JobAttribute>, String> {
+        match keyword {
+            "all" => {
+                let mut attrs = JobAttribute::template();
+                attrs.append(&mut JobAttribute::description());
+                Ok(attrs)
+            }
+            "job-description" => Ok(JobAttribute::description()),
+            "job-template" => Ok(JobAttribute::template()),
+            keyword => JobAttribute::try_from(keyword).map(|attr| vec![attr]),
+        }
+    }
+
+    // Resolves a list of requested-attributes keywords. Returns the selected attributes, without
+    // duplicates, along with any keywords that were not recognised.
+    pub fn from_keywords(keywords: &[String]) -> (Vec<JobAttribute>, Vec<String>) {
+        let mut attrs = Vec::<JobAttribute>::new();
+        let mut unknown = Vec::<String>::new();
+        for keyword in keywords {
+            match JobAttribute::expand(keyword) {
+                Ok(expanded) => {
+                    for attr in expanded {
+                        if !attrs.contains(&attr) {
+                            attrs.push(attr);
+                        }
+                    }
+                }
+                Err(_) => unknown.push(keyword.clone()),
+            }
+        }
+        (attrs, unknown)
+    }
 }
 
 impl From<JobAttribute> for String {
     fn from(a: JobAttribute) -> Self {
         match a {
+            JobAttribute::AttributesCharset => String::from("attributes-charset"),
+            JobAttribute::AttributesNaturalLanguage => String::from("attributes-natural-language"),
             JobAttribute::JobId => String::from("job-id"),
-            JobAttribute::JobUri => String::from("job-uri"),
+            JobAttribute::JobKOctets => String::from("job-k-octets"),
+            JobAttribute::JobName => String::from("job-name"),
+            JobAttribute::JobOriginatingUserName => String::from("job-originating-user-name"),
+            JobAttribute::JobPrinterUpTime => String::from("job-printer-up-time"),
+            JobAttribute::JobPrinterUri => String::from("job-printer-uri"),
             JobAttribute::JobState => String::from("job-state"),
             JobAttribute::JobStateReasons => String::from("job-state-reasons"),
+            JobAttribute::JobUri => String::from("job-uri"),
+            JobAttribute::NumberOfDocuments => String::from("number-of-documents"),
+            JobAttribute::NumberOfInterveningJobs => String::from("number-of-intervening-jobs"),
+            JobAttribute::TimeAtCompleted => String::from("time-at-completed"),
+            JobAttribute::TimeAtCreation => String::from("time-at-creation"),
+            JobAttribute::TimeAtProcessing => String::from("time-at-processing"),
+            JobAttribute::DateTimeAtCompleted => String::from("date-time-at-completed"),
+            JobAttribute::DateTimeAtCreation => String::from("date-time-at-creation"),
+            JobAttribute::DateTimeAtProcessing => String::from("date-time-at-processing"),
+            JobAttribute::DocumentFormatDetected => String::from("document-format-detected"),
+            JobAttribute::JobHoldUntil => String::from("job-hold-until"),
+            JobAttribute::JobHoldUntilTime => String::from("job-hold-until-time"),
         }
     }
 }
+
+impl TryFrom<&str> for JobAttribute {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        JobAttribute::description()
+            .into_iter()
+            .chain(JobAttribute::template())
+            .find(|&attr| String::from(attr) == value)
+            .ok_or_else(|| format!("Unknown Job Attribute {}", value))
+    }
+}
@@ -1,10 +1,12 @@
 use crate::ippresponse::attributes::{JobAttribute, PrinterAttribute};
-use crate::printer::{IppVersion, Job, Printer};
-use ipp::proto::attribute::IppAttribute;
+use crate::printer::{IppVersion, Job, Printer, Timestamp};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use ipp::proto::attribute::{IppAttribute, IppAttributeGroup};
 use ipp::proto::model::{DelimiterTag, StatusCode};
 use ipp::proto::request::IppRequestResponse;
 use ipp::proto::value::IppValue;
 use std::convert::Infallible;
+use std::time::SystemTime;
 
 pub struct IppResponseBuilder {
     version: IppVersion,
@@ -12,7 +14,7 @@ pub struct IppResponseBuilder {
     operational_attributes: Vec<IppAttribute>,
     unsupported_attributes: Vec<IppAttribute>,
     printer_attributes: Vec<IppAttribute>,
-    job_attributes: Vec<IppAttribute>,
+    job_attributes: Vec<Vec<IppAttribute>>,
     request_id: u32,
 }
 
@@ -29,45 +31,93 @@ impl IppResponseBuilder {
         }
     }
 
-    pub fn add_job_attribute(&mut self, job: &Job, attribute: JobAttribute) {
-        let attr = job.protofy_attribute(attribute).unwrap();
-        self.job_attributes.push(attr);
+    pub fn add_job_attribute(&mut self, printer: &Printer, job: &Job, attribute: JobAttribute) {
+        let attr = job.protofy_attribute(printer, attribute).unwrap();
+        if self.job_attributes.is_empty() {
+            self.add_job_group();
+        }
+        self.job_attributes.last_mut().unwrap().push(attr);
+    }
+
+    // Starts a new Job Attributes group. Subsequent job attributes are added to this group.
+    // Responses describing several jobs (such as Get-Jobs) use one group per job.
+    pub fn add_job_group(&mut self) {
+        self.job_attributes.push(Vec::new());
     }
 
-    pub fn add_required_job_attributes(&mut self, job: &Job) {
+    pub fn add_required_job_attributes(&mut self, printer: &Printer, job: &Job) {
         // IPP/1.1 Attributes
-        self.add_job_attribute(job, JobAttribute::JobId);
-        self.add_job_attribute(job, JobAttribute::JobUri);
-        self.add_job_attribute(job, JobAttribute::JobState);
-        self.add_job_attribute(job, JobAttribute::JobStateReasons);
+        self.add_job_attribute(printer, job, JobAttribute::JobId);
+        self.add_job_attribute(printer, job, JobAttribute::JobUri);
+        self.add_job_attribute(printer, job, JobAttribute::JobState);
+        self.add_job_attribute(printer, job, JobAttribute::JobStateReasons);
+    }
+
+    pub fn add_requested_job_attributes(
+        &mut self,
+        printer: &Printer,
+        job: &Job,
+        attributes: &[JobAttribute],
+    ) {
+        for &attr in attributes {
+            self.add_job_attribute(printer, job, attr);
+        }
+    }
+
+    // Supplies a short human-readable description of the status code.
+    // https://tools.ietf.org/html/rfc8011#section-4.1.6.2
+    pub fn add_status_message(&mut self, message: &str) {
+        self.operational_attributes.push(IppAttribute::new(
+            "status-message",
+            IppValue::TextWithoutLanguage(String::from(message)),
+        ));
+    }
+
+    // Reports an attribute, or attribute value, which the printer does not support.
+    // https://tools.ietf.org/html/rfc8011#section-4.1.7
+    pub fn add_unsupported_attribute(&mut self, attr: IppAttribute) {
+        self.unsupported_attributes.push(attr);
+        if self.status == StatusCode::SuccessfulOK {
+            self.status = StatusCode::SuccessfulOKIgnoredOrSubstitutedAttributes;
+        }
+    }
+
+    // Reports requested-attributes keywords which were ignored because the printer does not
+    // support them.
+    pub fn add_unsupported_requested_attributes(&mut self, keywords: &[String]) {
+        if keywords.is_empty() {
+            return;
+        }
+        let values = keywords
+            .iter()
+            .map(|keyword| IppValue::Keyword(keyword.clone()))
+            .collect();
+        self.add_unsupported_attribute(IppAttribute::new(
+            "requested-attributes",
+            IppValue::Array(values),
+        ));
     }
 
     pub fn add_printer_attribute(&mut self, printer: &Printer, attr: PrinterAttribute) {
+        // A printer which cannot fetch documents has no reference-uri-schemes-supported to report,
+        // and an attribute with no values cannot be encoded.
+        if matches!(attr, PrinterAttribute::ReferenceUriSchemesSupported)
+            && printer.fetchers.schemes().is_empty()
+        {
+            return;
+        }
         let attr = printer.protofy_attribute(attr).unwrap();
         self.printer_attributes.push(attr);
     }
 
-    pub fn add_required_printer_attributes(&mut self, printer: &Printer) {
-        // IPP/1.1 Attributes
-        self.add_printer_attribute(printer, PrinterAttribute::CharsetConfigured);
-        self.add_printer_attribute(printer, PrinterAttribute::CharsetSupported);
-        self.add_printer_attribute(printer, PrinterAttribute::CompressionSupported);
-        self.add_printer_attribute(printer, PrinterAttribute::DocumentFormatDefault);
-        self.add_printer_attribute(printer, PrinterAttribute::DocumentFormatSupported);
-        self.add_printer_attribute(printer, PrinterAttribute::GeneratedNaturalLanguageSupported);
-        self.add_printer_attribute(printer, PrinterAttribute::IppVersionsSupported);
-        self.add_printer_attribute(printer, PrinterAttribute::NaturalLanguageConfigured);
-        self.add_printer_attribute(printer, PrinterAttribute::OperationsSupported);
-        self.add_printer_attribute(printer, PrinterAttribute::PdlOverrideSupported);
-        self.add_printer_attribute(printer, PrinterAttribute::PrinterIsAcceptingJobs);
-        self.add_printer_attribute(printer, PrinterAttribute::PrinterName);
-        self.add_printer_attribute(printer, PrinterAttribute::PrinterState);
-        self.add_printer_attribute(printer, PrinterAttribute::PrinterStateReasons);
-        self.add_printer_attribute(printer, PrinterAttribute::PrinterUpTime);
-        self.add_printer_attribute(printer, PrinterAttribute::PrinterUriSupported);
-        self.add_printer_attribute(printer, PrinterAttribute::QueuedJobCount);
-        self.add_printer_attribute(printer, PrinterAttribute::UriAuthenticationSupported);
-        self.add_printer_attribute(printer, PrinterAttribute::UriSecuritySupported);
+    pub fn add_requested_printer_attributes(
+        &mut self,
+        printer: &Printer,
+        attributes: &[PrinterAttribute],
+    ) {
+        for &attr in attributes {
+            self.add_printer_attribute(printer, attr);
+        }
     }
 
     pub fn build(&self) -> Result<IppRequestResponse, Infallible> {
@@ -93,9 +143,14 @@ impl IppResponseBuilder {
                 .add(DelimiterTag::PrinterAttributes, attr.clone());
         }
 
-        for attr in &self.job_attributes {
-            resp.attributes_mut()
-                .add(DelimiterTag::JobAttributes, attr.clone());
+        for attrs in &self.job_attributes {
+            let mut group = IppAttributeGroup::new(DelimiterTag::JobAttributes);
+            for attr in attrs {
+                group
+                    .attributes_mut()
+                    .insert(String::from(attr.name()), attr.clone());
+            }
+            resp.attributes_mut().groups_mut().push(group);
         }
 
         Ok(resp)
@@ -205,7 +260,7 @@ impl Printer {
             }
             PrinterAttribute::PrinterUpTime => Ok(IppAttribute::new(
                 "printer-up-time",
-                IppValue::Integer(self.printer_up_time as i32),
+                IppValue::Integer(self.printer_up_time() as i32),
             )),
             PrinterAttribute::PrinterUriSupported => {
                 let mut uris = Vec::<IppValue>::new();
@@ -221,6 +276,16 @@ impl Printer {
                 "queued-job-count",
                 IppValue::Integer(self.queued_job_count() as i32),
             )),
+            PrinterAttribute::ReferenceUriSchemesSupported => {
+                let mut schemes = Vec::<IppValue>::new();
+                for scheme in self.fetchers.schemes() {
+                    schemes.push(IppValue::UriScheme(scheme));
+                }
+                Ok(IppAttribute::new(
+                    "reference-uri-schemes-supported",
+                    IppValue::Array(schemes),
+                ))
+            }
             PrinterAttribute::UriAuthenticationSupported => {
                 let mut auth_methods = Vec::<IppValue>::new();
                 for uri in &self.printer_uri_supported {
@@ -241,35 +306,173 @@ impl Printer {
                     IppValue::Array(sec_methods),
                 ))
             }
+            PrinterAttribute::WhichJobsSupported => {
+                let mut which_jobs = Vec::<IppValue>::new();
+                for &which in &self.which_jobs_supported {
+                    which_jobs.push(IppValue::Keyword(String::from(which)));
+                }
+                Ok(IppAttribute::new(
+                    "which-jobs-supported",
+                    IppValue::Array(which_jobs),
+                ))
+            }
+            PrinterAttribute::JobHoldUntilDefault => Ok(IppAttribute::new(
+                "job-hold-until-default",
+                IppValue::Keyword(String::from(self.job_hold_until_default)),
+            )),
+            PrinterAttribute::JobHoldUntilSupported => {
+                let mut values = Vec::<IppValue>::new();
+                for &until in &self.job_hold_until_supported {
+                    values.push(IppValue::Keyword(String::from(until)));
+                }
+                Ok(IppAttribute::new(
+                    "job-hold-until-supported",
+                    IppValue::Array(values),
+                ))
+            }
+            PrinterAttribute::JobIdsSupported => Ok(IppAttribute::new(
+                "job-ids-supported",
+                IppValue::Boolean(true),
+            )),
         }
     }
 }
 
 impl Job {
-    fn protofy_attribute(&self, attribute: JobAttribute) -> Result<IppAttribute, String> {
+    fn protofy_attribute(
+        &self,
+        printer: &Printer,
+        attribute: JobAttribute,
+    ) -> Result<IppAttribute, String> {
+        let name = String::from(attribute);
         match attribute {
-            JobAttribute::JobId => Ok(IppAttribute::new(
-                "job-id",
-                IppValue::Integer(self.id as i32),
+            JobAttribute::AttributesCharset => Ok(IppAttribute::new(
+                name,
+                IppValue::Charset(String::from(printer.charset_configured)),
             )),
-            JobAttribute::JobUri => Ok(IppAttribute::new(
-                "job-uri",
-                IppValue::Uri(self.uri.clone()),
+            JobAttribute::AttributesNaturalLanguage => Ok(IppAttribute::new(
+                name,
+                IppValue::NaturalLanguage(String::from(printer.natural_language_configured)),
             )),
-            JobAttribute::JobState => Ok(IppAttribute::new(
-                "job-state",
-                IppValue::Enum(self.state as i32),
+            JobAttribute::JobId => Ok(IppAttribute::new(name, IppValue::Integer(self.id as i32))),
+            JobAttribute::JobKOctets => Ok(IppAttribute::new(
+                name,
+                IppValue::Integer(self.k_octets() as i32),
             )),
+            JobAttribute::JobName => Ok(IppAttribute::new(
+                name,
+                IppValue::NameWithoutLanguage(self.name.clone()),
+            )),
+            JobAttribute::JobOriginatingUserName => Ok(IppAttribute::new(
+                name,
+                IppValue::NameWithoutLanguage(self.originating_user_name.clone()),
+            )),
+            JobAttribute::JobPrinterUpTime => Ok(IppAttribute::new(
+                name,
+                IppValue::Integer(printer.printer_up_time() as i32),
+            )),
+            JobAttribute::JobPrinterUri => {
+                let uri = printer
+                    .printer_uri_supported
+                    .first()
+                    .ok_or_else(|| String::from("Printer has no URI"))?;
+                Ok(IppAttribute::new(name, IppValue::Uri(uri.uri.clone())))
+            }
+            JobAttribute::JobState => {
+                Ok(IppAttribute::new(name, IppValue::Enum(self.state() as i32)))
+            }
             JobAttribute::JobStateReasons => {
                 let mut reasons = Vec::<IppValue>::new();
-                for &reason in &self.state_reasons {
+                for &reason in self.state_reasons() {
                     reasons.push(IppValue::Keyword(String::from(reason)));
                 }
-                Ok(IppAttribute::new(
-                    "job-state-reasons",
-                    IppValue::Array(reasons),
-                ))
+                Ok(IppAttribute::new(name, IppValue::Array(reasons)))
             }
+            JobAttribute::JobUri => Ok(IppAttribute::new(name, IppValue::Uri(self.uri.clone()))),
+            JobAttribute::NumberOfDocuments => Ok(IppAttribute::new(
+                name,
+                IppValue::Integer(self.documents.len() as i32),
+            )),
+            JobAttribute::NumberOfInterveningJobs => Ok(IppAttribute::new(
+                name,
+                IppValue::Integer(printer.intervening_job_count(self) as i32),
+            )),
+            JobAttribute::TimeAtCompleted => Ok(IppAttribute::new(
+                name,
+                protofy_up_time(printer, self.time_at_completed()),
+            )),
+            JobAttribute::TimeAtCreation => Ok(IppAttribute::new(
+                name,
+                protofy_up_time(printer, Some(self.time_at_creation())),
+            )),
+            JobAttribute::TimeAtProcessing => Ok(IppAttribute::new(
+                name,
+                protofy_up_time(printer, self.time_at_processing()),
+            )),
+            JobAttribute::DateTimeAtCompleted => Ok(IppAttribute::new(
+                name,
+                protofy_date_time(self.time_at_completed().map(|t| t.system_time())),
+            )),
+            JobAttribute::DateTimeAtCreation => Ok(IppAttribute::new(
+                name,
+                protofy_date_time(Some(self.time_at_creation().system_time())),
+            )),
+            JobAttribute::DateTimeAtProcessing => Ok(IppAttribute::new(
+                name,
+                protofy_date_time(self.time_at_processing().map(|t| t.system_time())),
+            )),
+            // One value for each document whose format was detected.
+            JobAttribute::DocumentFormatDetected => {
+                let formats: Vec<IppValue> = self
+                    .documents
+                    .iter()
+                    .filter_map(|d| d.format_detected)
+                    .map(|format| IppValue::MimeMediaType(String::from(format)))
+                    .collect();
+                let value = if formats.is_empty() {
+                    IppValue::NoValue
+                } else {
+                    IppValue::Array(formats)
+                };
+                Ok(IppAttribute::new(name, value))
+            }
+            JobAttribute::JobHoldUntil => Ok(IppAttribute::new(
+                name,
+                IppValue::Keyword(String::from(self.job_hold_until)),
+            )),
+            JobAttribute::JobHoldUntilTime => Ok(IppAttribute::new(
+                name,
+                protofy_date_time(self.job_hold_until_time),
+            )),
         }
     }
 }
+
+// Events which have not happened yet are reported with the out-of-band 'no-value'.
+// https://tools.ietf.org/html/rfc8011#section-5.3.14
+fn protofy_up_time(printer: &Printer, t: Option<Timestamp>) -> IppValue {
+    match t {
+        Some(t) => IppValue::Integer(printer.up_time_at(&t) as i32),
+        None => IppValue::NoValue,
+    }
+}
+
+// https://tools.ietf.org/html/rfc8010#section-3.9
+fn protofy_date_time(t: Option<SystemTime>) -> IppValue {
+    let t = match t {
+        Some(t) => DateTime::<Utc>::from(t),
+        None => return IppValue::NoValue,
+    };
+    IppValue::DateTime {
+        year: t.year() as u16,
+        month: t.month() as u8,
+        day: t.day() as u8,
+        hour: t.hour() as u8,
+        minutes: t.minute() as u8,
+        seconds: t.second() as u8,
+        deciseconds: (t.timestamp_subsec_millis() / 100) as u8,
+        utcdir: '+',
+        utchours: 0,
+        utcmins: 0,
+    }
+}
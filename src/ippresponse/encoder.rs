@@ -0,0 +1,37 @@
+use ipp::proto::attribute::IppAttribute;
+use ipp::proto::model::DelimiterTag;
+use ipp::proto::request::IppRequestResponse;
+
+// Operation attributes which must lead the response, in this order.
+// https://tools.ietf.org/html/rfc8011#section-4.1.4
+const HEADER_ATTRS: [&str; 2] = ["attributes-charset", "attributes-natural-language"];
+
+// Serializes a response including every attribute group in order. Unlike
+// IppRequestResponse::to_bytes, this supports repeated groups (such as one Job group per job in a
+// Get-Jobs response) and the unsupported attributes group.
+// https://tools.ietf.org/html/rfc8010#section-3.1.1
+pub fn to_bytes(resp: &IppRequestResponse) -> Vec<u8> {
+    let mut buffer = Vec::<u8>::new();
+    buffer.extend_from_slice(&resp.header().to_bytes());
+
+    for group in resp.attributes().groups() {
+        buffer.push(group.tag() as u8);
+
+        let mut attrs: Vec<&IppAttribute> = group.attributes().values().collect();
+        if group.tag() == DelimiterTag::OperationAttributes {
+            attrs.sort_by_key(|attr| {
+                HEADER_ATTRS
+                    .iter()
+                    .position(|&name| name == attr.name())
+                    .unwrap_or(HEADER_ATTRS.len())
+            });
+        }
+
+        for attr in attrs {
+            buffer.extend_from_slice(&attr.to_bytes());
+        }
+    }
+    buffer.push(DelimiterTag::EndOfAttributes as u8);
+
+    buffer
+}
@@ -1,5 +1,7 @@
-pub use attributes::PrinterAttribute;
+pub use attributes::{JobAttribute, PrinterAttribute};
 pub use builder::IppResponseBuilder;
+pub use encoder::to_bytes;
 
 mod attributes;
 mod builder;
+mod encoder;
@@ -0,0 +1,495 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use ipp::proto::model::StatusCode;
+use log::warn;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+use crate::printer::{
+    Compression, Job, MimeMediaType, Operation, Printer, PrinterState, WhichJobs,
+};
+use crate::spool::{ReceiveError, Spool};
+use crate::timeout::IdleTimeout;
+use crate::{ANONYMOUS_USER_NAME, DEFAULT_JOB_NAME};
+
+// Commands and subcommands are single lines; anything longer is not a valid request.
+const MAX_LINE_LENGTH: u64 = 1024;
+// Control files hold a few short lines each, so anything larger is refused.
+const MAX_CONTROL_FILE_SIZE: u64 = 64 * 1024;
+// How long a client may stay silent before its connection is dropped.
+const READ_TIMEOUT: Duration = Duration::from_secs(300);
+// How long to wait before accepting connections again after accepting one failed, e.g. because
+// the process ran out of file descriptors.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+type Stream = BufReader<IdleTimeout<TcpStream>>;
+
+// Accepts jobs from LPD clients such as lpr, and answers lpq with the same queue IPP clients see.
+// Every queue name refers to this printer.
+// https://tools.ietf.org/html/rfc1179
+pub async fn run(mx_printer: Arc<RwLock<Printer>>, addr: SocketAddr) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    println!("Listening for LPD requests on {}", addr);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Could not accept an LPD connection: {}", e);
+                tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                continue;
+            }
+        };
+        let mx_printer = Arc::clone(&mx_printer);
+        tokio::spawn(async move {
+            if let Err(e) = handle(&mx_printer, stream, peer.ip()).await {
+                warn!("LPD request from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+// Requests are checked against the printer's policy as the IPP operations they correspond to.
+// https://tools.ietf.org/html/rfc1179#section-5
+async fn handle(
+    mx_printer: &Arc<RwLock<Printer>>,
+    stream: TcpStream,
+    peer: IpAddr,
+) -> io::Result<()> {
+    let mut stream = BufReader::new(IdleTimeout::new(stream, READ_TIMEOUT));
+    let line = match read_line(&mut stream).await? {
+        Some(line) => line,
+        None => return Ok(()),
+    };
+    let (command, operands) = split_command(&line)?;
+    match command {
+        // Print any waiting jobs. Jobs are always processed as soon as they are received.
+        0x01 => Ok(()),
+        0x02 => receive_job(mx_printer, &mut stream, peer).await,
+        0x03 | 0x04 => {
+            let filter: Vec<&str> = operands.iter().skip(1).copied().collect();
+            let state = {
+                let printer = mx_printer.read().unwrap();
+                if let Err(status) = printer.policy.check(Operation::GetJobs, None, None, peer) {
+                    return Err(denied(status));
+                }
+                queue_state(&printer, &filter, command == 0x04)
+            };
+            stream.write_all(state.as_bytes()).await
+        }
+        _ => {
+            warn!("Unsupported LPD command {:#04x}", command);
+            Ok(())
+        }
+    }
+}
+
+// Receives the control and data files of a job, then queues the job once the client closes the
+// connection. Files may arrive in any order. Data files are written to the spool as they arrive.
+// https://tools.ietf.org/html/rfc1179#section-6
+async fn receive_job(
+    mx_printer: &Arc<RwLock<Printer>>,
+    stream: &mut Stream,
+    peer: IpAddr,
+) -> io::Result<()> {
+    // The user is only known once the control file arrives, but a client at an address which may
+    // not print is turned away before it sends anything.
+    let (permitted, accepting, max_size, supported, spool) = {
+        let printer = mx_printer.read().unwrap();
+        (
+            printer.policy.check_address(Operation::PrintJob, peer),
+            printer.printer_is_accepting_jobs,
+            printer.max_document_size,
+            printer.document_format_supported.clone(),
+            Arc::clone(&printer.spool),
+        )
+    };
+    if let Err(status) = permitted {
+        reply(stream, false).await?;
+        return Err(denied(status));
+    }
+    if !accepting {
+        return reply(stream, false).await;
+    }
+    reply(stream, true).await?;
+
+    let mut data_files = HashMap::<String, DataFile>::new();
+    let received = receive_files(stream, &spool, max_size, &supported, &mut data_files).await;
+    let mut permitted = Ok(());
+    let job = match &received {
+        Ok(Some(control)) => {
+            let mut printer = mx_printer.write().unwrap();
+            let user = control.user.as_deref().filter(|user| !user.is_empty());
+            permitted = printer.policy.check(Operation::PrintJob, user, None, peer);
+            if permitted.is_ok() {
+                let job = queue_job(&mut printer, control, &data_files);
+                printer.sync_spool();
+                job
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    if job.is_some() {
+        spool.flush().await;
+    }
+
+    // Data files which no print command named, or which arrived for a job that was never queued,
+    // are not needed.
+    let documents: HashSet<&Path> = job
+        .iter()
+        .flat_map(|job| &job.documents)
+        .map(|document| document.path())
+        .collect();
+    for file in data_files.values() {
+        if !documents.contains(file.path.as_path()) {
+            spool.discard(&file.path).await;
+        }
+    }
+
+    permitted.map_err(denied)?;
+    match (received?, job) {
+        (Some(_), Some(job)) => println!("Created Job: {} (via LPD)", job.uri),
+        (Some(control), None) => warn!("LPD job {} had nothing to print", control.job_name()),
+        (None, _) => {}
+    }
+    Ok(())
+}
+
+fn denied(status: StatusCode) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("denied by policy: {:?}", status),
+    )
+}
+
+// A data file which has been written to the spool.
+struct DataFile {
+    path: PathBuf,
+    size: u64,
+    // The format the contents look like, for print commands which leave it open.
+    sniffed: MimeMediaType,
+}
+
+// Receives files until the client closes the connection, returning the control file. Returns None
+// if the client aborted the job or a file was refused. A file is refused if it completes a print
+// command whose format the printer does not support.
+async fn receive_files(
+    stream: &mut Stream,
+    spool: &Spool,
+    max_size: u64,
+    supported: &[MimeMediaType],
+    data_files: &mut HashMap<String, DataFile>,
+) -> io::Result<Option<ControlFile>> {
+    let mut control: Option<ControlFile> = None;
+    while let Some(line) = read_line(stream).await? {
+        let (subcommand, operands) = split_command(&line)?;
+        match subcommand {
+            // Abort job
+            0x01 => return Ok(None),
+            // Receive control file, receive data file
+            0x02 | 0x03 => {
+                let (count, name) = match operands.as_slice() {
+                    [count, name] => (count.parse::<u64>().ok(), String::from(*name)),
+                    _ => (None, String::new()),
+                };
+                let max_count = if subcommand == 0x02 {
+                    MAX_CONTROL_FILE_SIZE
+                } else {
+                    max_size
+                };
+                let count = match count {
+                    Some(count) if count <= max_count => count,
+                    _ => return reply(stream, false).await.map(|_| None),
+                };
+                reply(stream, true).await?;
+
+                if subcommand == 0x02 {
+                    let mut contents = Vec::new();
+                    (&mut *stream)
+                        .take(count)
+                        .read_to_end(&mut contents)
+                        .await?;
+                    if contents.len() as u64 != count {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    control = Some(ControlFile::parse(&String::from_utf8_lossy(&contents)));
+                } else {
+                    let file = receive_data_file(stream, spool, count).await?;
+                    if let Some(replaced) = data_files.insert(name, file) {
+                        spool.discard(&replaced.path).await;
+                    }
+                }
+                // Each file is followed by a single zero octet.
+                if stream.read_u8().await? != 0 {
+                    return reply(stream, false).await.map(|_| None);
+                }
+
+                if let Some(control) = &control {
+                    if let Some(format) = unsupported_format(control, data_files, supported) {
+                        warn!(
+                            "LPD job asks for unsupported format {}",
+                            String::from(format)
+                        );
+                        return reply(stream, false).await.map(|_| None);
+                    }
+                }
+                reply(stream, true).await?;
+            }
+            _ => return reply(stream, false).await.map(|_| None),
+        }
+    }
+    match control {
+        Some(control) => Ok(Some(control)),
+        None => Err(io::Error::other("connection closed without a control file")),
+    }
+}
+
+// Streams a data file of the given size into the spool.
+async fn receive_data_file(stream: &mut Stream, spool: &Spool, count: u64) -> io::Result<DataFile> {
+    let reader = (&mut *stream).take(count).compat();
+    let (path, size) = match spool
+        .receive_document(reader, Compression::None, count)
+        .await
+    {
+        Ok(document) => document,
+        Err(ReceiveError::Io(e)) | Err(ReceiveError::Compression(e)) => return Err(e),
+        Err(ReceiveError::TooLarge) => return Err(io::ErrorKind::InvalidData.into()),
+    };
+    if size != count {
+        spool.discard(&path).await;
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let sniffed = match spool.sniff(&path).await {
+        Ok(sniffed) => sniffed,
+        Err(e) => {
+            spool.discard(&path).await;
+            return Err(e);
+        }
+    };
+    Ok(DataFile {
+        path,
+        size,
+        sniffed,
+    })
+}
+
+// The first format asked for by a print command which the printer does not support, among the
+// commands whose data files have arrived.
+fn unsupported_format(
+    control: &ControlFile,
+    data_files: &HashMap<String, DataFile>,
+    supported: &[MimeMediaType],
+) -> Option<MimeMediaType> {
+    control
+        .print_commands
+        .iter()
+        .filter_map(|(command, name)| Some(format(*command, data_files.get(name)?.sniffed)))
+        .find(|format| !supported.contains(format))
+}
+
+// LPD has no notion of copies beyond repeating a print command, so each print command becomes a
+// document of its own and three copies of a file are three documents sharing the same data file.
+fn queue_job(
+    printer: &mut Printer,
+    control: &ControlFile,
+    data_files: &HashMap<String, DataFile>,
+) -> Option<Job> {
+    let documents: Vec<(MimeMediaType, &DataFile)> = control
+        .print_commands
+        .iter()
+        .filter_map(|(command, name)| {
+            let file = data_files.get(name)?;
+            Some((format(*command, file.sniffed), file))
+        })
+        .collect();
+    if documents.is_empty() {
+        return None;
+    }
+
+    let job_id = printer
+        .create_job(&control.job_name(), &control.user_name())
+        .id;
+    let job = printer.job_mut(job_id).unwrap();
+    let count = documents.len();
+    for (i, (format, file)) in documents.into_iter().enumerate() {
+        job.add_document(
+            format,
+            file.sniffed,
+            file.path.clone(),
+            file.size,
+            i + 1 == count,
+        );
+    }
+    Some(job.clone())
+}
+
+// https://tools.ietf.org/html/rfc1179#section-7
+fn format(command: char, sniffed: MimeMediaType) -> MimeMediaType {
+    match command {
+        // Print formatted file, print with 'pr' format
+        'f' | 'p' => MimeMediaType::PlainText,
+        // Print postscript output file
+        'o' => MimeMediaType::Postscript,
+        // Print file leaving control characters, and the obsolete formats
+        _ => sniffed,
+    }
+}
+
+// https://tools.ietf.org/html/rfc1179#section-7
+#[derive(Debug, Default)]
+struct ControlFile {
+    // P - User identification
+    user: Option<String>,
+    // J - Job name for banner page
+    job_name: Option<String>,
+    // N - Name of source file
+    source_name: Option<String>,
+    // The print commands, each with the name of the data file to print.
+    print_commands: Vec<(char, String)>,
+}
+
+impl ControlFile {
+    fn parse(contents: &str) -> Self {
+        let mut control = ControlFile::default();
+        for line in contents.lines() {
+            let mut chars = line.chars();
+            let (command, operand) = match chars.next() {
+                Some(command) => (command, String::from(chars.as_str())),
+                None => continue,
+            };
+            match command {
+                'P' => control.user = Some(operand),
+                'J' => control.job_name = Some(operand),
+                'N' if control.source_name.is_none() => control.source_name = Some(operand),
+                c if c.is_ascii_lowercase() => control.print_commands.push((c, operand)),
+                _ => {}
+            }
+        }
+        control
+    }
+
+    fn job_name(&self) -> String {
+        self.job_name
+            .iter()
+            .chain(self.source_name.iter())
+            .find(|name| !name.is_empty())
+            .cloned()
+            .unwrap_or_else(|| String::from(DEFAULT_JOB_NAME))
+    }
+
+    fn user_name(&self) -> String {
+        self.user
+            .clone()
+            .filter(|user| !user.is_empty())
+            .unwrap_or_else(|| String::from(ANONYMOUS_USER_NAME))
+    }
+}
+
+// Describes the queue in the style of BSD lpq. The list of user names and job numbers, if any,
+// restricts which jobs are shown.
+// https://tools.ietf.org/html/rfc1179#section-5.3
+fn queue_state(printer: &Printer, filter: &[&str], long: bool) -> String {
+    let status = match printer.printer_state {
+        PrinterState::Idle => "is ready",
+        PrinterState::Processing => "is ready and printing",
+        PrinterState::Stopped => "is stopped",
+    };
+    let mut state = format!("{} {}\n", printer.printer_name, status);
+
+    let jobs: Vec<&Job> = printer
+        .jobs_matching(WhichJobs::NotCompleted)
+        .into_iter()
+        .filter(|job| {
+            filter.is_empty()
+                || filter
+                    .iter()
+                    .any(|&f| f == job.originating_user_name || f.parse::<u32>() == Ok(job.id))
+        })
+        .collect();
+    if jobs.is_empty() {
+        state.push_str("no entries\n");
+        return state;
+    }
+
+    if !long {
+        state.push_str(&format!(
+            "{:<7}{:<11}{:<5}{:<38}{}\n",
+            "Rank", "Owner", "Job", "Files", "Total Size"
+        ));
+    }
+    let mut position = 0;
+    for job in jobs {
+        // Jobs which have not finished have a processing time only while they are being processed.
+        let rank = if job.time_at_processing().is_some() {
+            String::from("active")
+        } else {
+            position += 1;
+            ordinal(position)
+        };
+        let size: u64 = job.documents.iter().map(|d| d.size()).sum();
+        if long {
+            state.push_str(&format!(
+                "\n{}: {:<37}[job {}]\n        {:<32}{} bytes\n",
+                job.originating_user_name, rank, job.id, job.name, size
+            ));
+        } else {
+            state.push_str(&format!(
+                "{:<7}{:<11}{:<5}{:<38}{} bytes\n",
+                rank, job.originating_user_name, job.id, job.name, size
+            ));
+        }
+    }
+    state
+}
+
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+// Reads a command line, without its terminating line feed. Returns None at the end of the stream.
+async fn read_line(stream: &mut Stream) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::<u8>::new();
+    (&mut *stream)
+        .take(MAX_LINE_LENGTH)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.pop() != Some(b'\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "command line is too long or unterminated",
+        ));
+    }
+    Ok(Some(line))
+}
+
+// Splits a command line into its command code and its operands, which are separated by white space.
+fn split_command(line: &[u8]) -> io::Result<(u8, Vec<&str>)> {
+    let (&command, operands) = line
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "empty command"))?;
+    let operands =
+        std::str::from_utf8(operands).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok((command, operands.split_whitespace().collect()))
+}
+
+// A zero octet acknowledges a command; anything else refuses it.
+async fn reply(stream: &mut Stream, ok: bool) -> io::Result<()> {
+    stream.get_mut().write_u8(if ok { 0 } else { 1 }).await
+}
@@ -1,35 +1,72 @@
-use std::convert::Infallible;
-use std::net::SocketAddr;
+use std::convert::{Infallible, TryFrom};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
 
 use futures::future::BoxFuture;
-use futures::FutureExt;
-use hyper::body;
+use futures::{FutureExt, TryStreamExt};
+use hyper::server::conn::AddrStream;
 use hyper::service::{make_service_fn, service_fn};
-use hyper::{Body, Method, Request, Response, Server};
+use hyper::{header, Body, Method, Request, Response, Server};
 use ipp::prelude::*;
-use ipp::proto::parser::IppParser;
-use ipp::proto::reader::IppReader;
-use log::{info, trace, warn};
+use log::warn;
 use num_traits::FromPrimitive;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::compat::TokioAsyncReadCompatExt;
 
-use printer::Printer;
+use printer::{
+    Job, JobHoldUntil, JobStateReason, MimeMediaType, PdlOverride, Printer,
+    UriAuthenticationMethod, UriSecurityMethod, WhichJobs,
+};
 
-use crate::ippresponse::IppResponseBuilder;
+use crate::config::Config;
 
+use crate::error::ServerError;
+use crate::fetcher::{Document, FetchError};
+use crate::ipprequest::{ParseError, ParseErrorKind, ValidationError};
+use crate::ippresponse::{IppResponseBuilder, JobAttribute, PrinterAttribute};
+use crate::spool::ReceiveError;
+
+mod auth;
+mod backend;
+mod config;
+mod decompress;
+mod error;
+mod fetcher;
+mod ipprequest;
 mod ippresponse;
+mod lpd;
+mod policy;
 mod printer;
+mod processor;
+mod raw;
+mod scheduler;
+mod spool;
+mod timeout;
+mod tls;
+
+// https://tools.ietf.org/html/rfc8011#section-5.3.5
+const DEFAULT_JOB_NAME: &str = "Untitled";
+// https://tools.ietf.org/html/rfc8011#section-5.3.6
+const ANONYMOUS_USER_NAME: &str = "anonymous";
+// How much a request may carry in its header and attributes, which are held in memory, on top of
+// the largest document.
+const MAX_ATTRIBUTES_SIZE: u64 = 1024 * 1024;
 
-async fn print_ipp_request(req: &mut IppRequestResponse) {
+fn print_ipp_request(req: &IppRequestResponse) {
     println!("IPP Request:");
     let header = req.header();
     println!("OperationStatus (Raw): {:?}", header.operation_status);
     let operation = Operation::from_u16(header.operation_status);
     println!("OperationStatus: {:?}", operation);
     println!("Request ID: {}", header.request_id);
-    let version = parse_version(header.version).unwrap();
-    println!("Version: {}", version);
+    match parse_version(header.version) {
+        Ok(version) => println!("Version: {}", version),
+        Err(()) => println!("Version (Raw): {:#06x}", header.version.0),
+    }
 
     for attr in req.attributes().groups() {
         println!("Attribute Group:");
@@ -56,6 +93,12 @@ fn parse_version(v: IppVersion) -> Result<printer::IppVersion, ()> {
     }
 }
 
+// Requests are only handled in the versions of IPP the printer says it supports.
+// https://tools.ietf.org/html/rfc8011#section-4.1.8
+fn is_version_supported(printer: &Printer, v: IppVersion) -> bool {
+    parse_version(v).is_ok_and(|v| printer.ipp_versions_supported.contains(&v))
+}
+
 fn print_ipp_response(req: &IppRequestResponse) {
     println!("IPP Request:");
     let header = req.header();
@@ -63,8 +106,10 @@ fn print_ipp_response(req: &IppRequestResponse) {
     let status = StatusCode::from_u16(header.operation_status);
     println!("OperationStatus: {:?}", status);
     println!("Request ID: {}", header.request_id);
-    let version = parse_version(header.version).unwrap();
-    println!("Version: {}", version);
+    match parse_version(header.version) {
+        Ok(version) => println!("Version: {}", version),
+        Err(()) => println!("Version (Raw): {:#06x}", header.version.0),
+    }
 
     for attr in req.attributes().groups() {
         println!("Attribute Group:");
@@ -75,80 +120,245 @@ fn print_ipp_response(req: &IppRequestResponse) {
     }
 }
 
+// Handles a request from a client at the given address, received on the listener for the printer
+// URI with the given uri-security.
 async fn handle(
     mx_printer: &Arc<RwLock<Printer>>,
+    security: UriSecurityMethod,
+    peer: IpAddr,
     req: Request<Body>,
 ) -> Result<Response<Body>, Infallible> {
     println!("--- Request Received ---");
-    let method = req.method().as_str();
-    let path = req.uri().path();
-    let mut cont = false;
-    println!("{} {}", method, path);
-    for hdr in req.headers() {
-        let name = hdr.0.as_str();
-        let val = hdr.1.to_str().unwrap();
-
-        if name.to_lowercase() == "expect" && val.to_lowercase() == "100-continue" {
-            cont = true;
+    println!("{} {}", req.method(), req.uri().path());
+    for (name, val) in req.headers() {
+        if name == header::AUTHORIZATION {
+            println!("{}: <redacted>", name);
+            continue;
+        }
+        match val.to_str() {
+            Ok(val) => println!("{}: {}", name, val),
+            Err(_) => println!("{}: {:?}", name, val),
         }
-
-        println!("{}: {}", name, val);
     }
-    if req.method() != Method::POST || req.uri().path() != "/ipp/print" {
+    if req.method() != Method::POST || req.uri().path() != config::PRINTER_PATH {
         println!("Returning: 404 Not Found");
-        return Ok(Response::builder()
-            .status(404)
-            .body(Body::from("404 Not Found\n"))
-            .unwrap());
+        return Ok(http_response(404, Body::from("404 Not Found\n")));
     }
 
-    let (_parts, body) = req.into_parts();
-    let bytes = body::to_bytes(body).await.unwrap();
-    let parser = IppParser::new(IppReader::new(futures::io::Cursor::new(bytes)));
-    let resp_body = match parser.parse().await {
-        Ok(mut req) => {
-            let resp = handle_ipp(mx_printer, &mut req).await;
-            Body::from(resp.to_bytes().to_vec())
+    let user = match authenticate(mx_printer, security, &req) {
+        Ok(user) => user,
+        Err(challenge) => {
+            println!("Returning: 401 Unauthorized");
+            let mut response = http_response(401, Body::from("401 Unauthorized\n"));
+            if let Ok(challenge) = header::HeaderValue::from_str(&challenge) {
+                response
+                    .headers_mut()
+                    .insert(header::WWW_AUTHENTICATE, challenge);
+            }
+            return Ok(response);
+        }
+    };
+
+    let response = match handle_http(mx_printer, req, user, peer).await {
+        Ok(resp) => {
+            let mut response = http_response(200, Body::from(ippresponse::to_bytes(&resp)));
+            response.headers_mut().insert(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static("application/ipp"),
+            );
+            response
         }
         Err(e) => {
-            println!("Error: {:?}", e);
-            Body::from("Hello World")
+            warn!("{}", e);
+            http_response(e.http_status(), Body::from(format!("{}\n", e)))
+        }
+    };
+    println!("Response Status: {}", response.status());
+    Ok(response)
+}
+
+// Requests to a printer URI whose uri-authentication-supported is basic or digest must carry valid
+// credentials. Returns the authenticated user, if any, or the challenge to answer with.
+// https://tools.ietf.org/html/rfc8011#section-5.4.2
+fn authenticate(
+    mx_printer: &Arc<RwLock<Printer>>,
+    security: UriSecurityMethod,
+    req: &Request<Body>,
+) -> Result<Option<String>, String> {
+    let (scheme, authenticator) = {
+        let printer = mx_printer.read().unwrap();
+        let scheme = printer
+            .printer_uri_supported
+            .iter()
+            .find(|uri| uri.security == security)
+            .map(|uri| uri.authentication);
+        (scheme, printer.authenticator.clone())
+    };
+    match (scheme, authenticator) {
+        (Some(scheme @ UriAuthenticationMethod::Basic), Some(authenticator))
+        | (Some(scheme @ UriAuthenticationMethod::Digest), Some(authenticator)) => {
+            authenticator.authenticate(scheme, req).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn http_response(status: u16, body: Body) -> Response<Body> {
+    let mut response = Response::new(body);
+    *response.status_mut() =
+        hyper::StatusCode::from_u16(status).unwrap_or(hyper::StatusCode::INTERNAL_SERVER_ERROR);
+    response
+}
+
+// Reads the IPP request from the HTTP body. Errors which occur before an IPP header has been read
+// are returned to be reported at the HTTP level. An operation which is rejected once the header
+// and attributes have been read is answered without reading the document which follows them.
+// https://tools.ietf.org/html/rfc8010#section-4.1
+async fn handle_http(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: Request<Body>,
+    user: Option<String>,
+    peer: IpAddr,
+) -> Result<IppRequestResponse, ServerError> {
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|val| String::from_utf8_lossy(val.as_bytes()).into_owned())
+        .unwrap_or_default();
+    let media_type = content_type.split(';').next().unwrap_or_default().trim();
+    if !media_type.eq_ignore_ascii_case("application/ipp") {
+        return Err(ServerError::UnsupportedMediaType(content_type));
+    }
+
+    // Everything which can be decided from the HTTP header is checked before the body is read.
+    // Reading the body is what answers Expect: 100-continue, so a client waiting for permission to
+    // send a large document gets a final status instead.
+    // https://tools.ietf.org/html/rfc7231#section-5.1.1
+    if let Some(expect) = req.headers().get(header::EXPECT) {
+        if !expect.as_bytes().eq_ignore_ascii_case(b"100-continue") {
+            let expect = String::from_utf8_lossy(expect.as_bytes()).into_owned();
+            return Err(ServerError::ExpectationFailed(expect));
+        }
+    }
+    let max_size = mx_printer.read().unwrap().max_document_size + MAX_ATTRIBUTES_SIZE;
+    let content_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| val.parse::<u64>().ok());
+    if content_length.is_some_and(|length| length > max_size) {
+        return Err(ServerError::RequestTooLarge(max_size));
+    }
+
+    // The body is parsed as it arrives. Only the attributes are read here; a document which follows
+    // them is left to the operation, which writes it to the spool.
+    let body = req.into_body().map_err(io::Error::other).into_async_read();
+    let mut req = match ipprequest::parse(body, MAX_ATTRIBUTES_SIZE).await {
+        Ok(req) => req,
+        Err(ParseError { request_id, error }) => {
+            let e = match error {
+                ParseErrorKind::Invalid(error) => {
+                    ServerError::MalformedRequest(format!("{:?}", error))
+                }
+                ParseErrorKind::TooLarge(limit) => ServerError::RequestTooLarge(limit),
+            };
+            // If the header could be read the failure can still be reported in an IPP response.
+            return match request_id {
+                Some(request_id) => {
+                    warn!("{}", e);
+                    Ok(error_response(e, request_id))
+                }
+                None => Err(e),
+            };
         }
     };
-    let status = if cont { 100 } else { 200 };
-    println!("Response Status: {}", status);
-    Ok(Response::builder().status(200).body(resp_body).unwrap())
+
+    if let Some(user) = user {
+        ipprequest::set_requesting_user_name(&mut req, &user);
+    }
+
+    Ok(handle_ipp(mx_printer, &mut req, peer).await)
 }
 
-impl Printer {}
+fn error_response(e: ServerError, request_id: u32) -> IppRequestResponse {
+    println!("Rejecting request: {:?}", e.status_code());
+    let mut builder = IppResponseBuilder::new(e.status_code(), request_id);
+    builder.add_status_message(&e.to_string());
+    builder.build().unwrap()
+}
 
 async fn handle_ipp(
     mx_printer: &Arc<RwLock<Printer>>,
     req: &mut IppRequestResponse,
+    peer: IpAddr,
 ) -> IppRequestResponse {
-    print_ipp_request(req).await;
-
-    let operation: Operation = Operation::from_u16(req.header().operation_status).unwrap();
-    let response: BoxFuture<Result<IppRequestResponse, Infallible>> =
-        if operation == Operation::GetPrinterAttributes {
-            handle_get_printer_attributes(mx_printer, req).boxed()
-        } else if operation == Operation::ValidateJob {
-            handle_validate_job(mx_printer, req).boxed()
-        } else if operation == Operation::PrintJob {
-            handle_print_job(mx_printer, req).boxed()
-        } else {
-            async {
-                let header = req.header();
-                let builder = IppResponseBuilder::new(
-                    StatusCode::ServerErrorOperationNotSupported,
-                    header.request_id,
-                );
-                Ok(builder.build().unwrap())
+    print_ipp_request(req);
+
+    let header = req.header();
+    let request_id = header.request_id;
+    let operation_id = header.operation_status;
+    let version_supported = is_version_supported(&mx_printer.read().unwrap(), header.version);
+    let denied = match printer::Operation::try_from(operation_id) {
+        Ok(operation) if version_supported => authorize(mx_printer, operation, req, peer).err(),
+        _ => None,
+    };
+    let response: BoxFuture<Result<IppRequestResponse, ServerError>> = if let Some(e) = denied {
+        let resp = validation_error_response(e, request_id);
+        async move { Ok(resp) }.boxed()
+    } else if !version_supported {
+        let version = header.version.0;
+        async move { Err(ServerError::VersionNotSupported(version)) }.boxed()
+    } else {
+        match printer::Operation::try_from(operation_id) {
+            // Documents supplied by reference are only accepted where they may be fetched from.
+            Ok(operation @ (printer::Operation::PrintUri | printer::Operation::SendUri))
+                if !mx_printer
+                    .read()
+                    .unwrap()
+                    .operations_supported
+                    .contains(&operation) =>
+            {
+                async move { Err(ServerError::OperationNotSupported(operation_id)) }.boxed()
             }
-            .boxed()
-        };
+            Ok(printer::Operation::GetPrinterAttributes) => {
+                handle_get_printer_attributes(mx_printer, req).boxed()
+            }
+            Ok(printer::Operation::ValidateJob) => handle_validate_job(mx_printer, req).boxed(),
+            Ok(printer::Operation::PrintJob) => handle_print_job(mx_printer, req).boxed(),
+            Ok(printer::Operation::PrintUri) => handle_print_uri(mx_printer, req).boxed(),
+            Ok(printer::Operation::CreateJob) => handle_create_job(mx_printer, req).boxed(),
+            Ok(printer::Operation::SendDocument) => handle_send_document(mx_printer, req).boxed(),
+            Ok(printer::Operation::SendUri) => handle_send_uri(mx_printer, req).boxed(),
+            Ok(printer::Operation::CancelJob) => handle_cancel_job(mx_printer, req).boxed(),
+            Ok(printer::Operation::GetJobAttributes) => {
+                handle_get_job_attributes(mx_printer, req).boxed()
+            }
+            Ok(printer::Operation::GetJobs) => handle_get_jobs(mx_printer, req).boxed(),
+            Ok(printer::Operation::HoldJob) => handle_hold_job(mx_printer, req).boxed(),
+            Ok(printer::Operation::ReleaseJob) => handle_release_job(mx_printer, req).boxed(),
+            Ok(printer::Operation::RestartJob) => handle_restart_job(mx_printer, req).boxed(),
+            Ok(printer::Operation::ReprocessJob) => handle_reprocess_job(mx_printer, req).boxed(),
+            Ok(printer::Operation::PausePrinter) => handle_pause_printer(mx_printer, req).boxed(),
+            Ok(printer::Operation::ResumePrinter) => handle_resume_printer(mx_printer, req).boxed(),
+            Ok(printer::Operation::PurgeJobs) => handle_purge_jobs(mx_printer, req).boxed(),
+            _ => async move { Err(ServerError::OperationNotSupported(operation_id)) }.boxed(),
+        }
+    };
 
-    let resp: IppRequestResponse = response.await.unwrap();
+    let resp = match response.await {
+        Ok(resp) => resp,
+        Err(e) => {
+            warn!("{}", e);
+            error_response(e, request_id)
+        }
+    };
+
+    let spool = {
+        let mut printer = mx_printer.write().unwrap();
+        printer.sync_spool();
+        Arc::clone(&printer.spool)
+    };
+    spool.flush().await;
 
     println!("Sending Response:");
     print_ipp_response(&resp);
@@ -158,122 +368,1244 @@ async fn handle_ipp(
 async fn handle_get_printer_attributes(
     mx_printer: &Arc<RwLock<Printer>>,
     req: &IppRequestResponse,
-) -> Result<IppRequestResponse, Infallible> {
-    // Find the OperationAttributes attribute group(s)
-    let mut requested_attr_keywords: Vec<String> = vec![];
-    for group in req
-        .attributes()
-        .groups_of(DelimiterTag::OperationAttributes)
-    {
-        // Find the list of requested attributes
-        match group.attributes().get("requested-attributes") {
-            Some(attr) => match attr.value() {
-                IppValue::Array(values) => {
-                    for keyword_value in values {
-                        match keyword_value {
-                            IppValue::Keyword(keyword) => {
-                                requested_attr_keywords.push(keyword.clone())
-                            }
-                            _ => warn!(
-                                "Found unexpected value type in requested-attributes: {:?}",
-                                keyword_value
-                            ),
-                        }
-                    }
-                }
-                attr => warn!(
-                    "Found unexpected value attribute value for requested-attributes: {:?}",
-                    attr
-                ),
-            },
-            None => warn!("Did not find requested-attributes in OperationAttributes group."),
-        }
-    }
+) -> Result<IppRequestResponse, ServerError> {
+    // If requested-attributes is omitted the printer behaves as though "all" were supplied.
+    let requested_attr_keywords =
+        ipprequest::requested_attributes(req).unwrap_or_else(|| vec![String::from("all")]);
 
     println!(
         "The client has requested these attributes: {}",
         requested_attr_keywords.join(",")
     );
 
+    let (requested_attrs, unknown_keywords) =
+        PrinterAttribute::from_keywords(&requested_attr_keywords);
+
     let header = req.header();
     let mut builder = IppResponseBuilder::new(StatusCode::SuccessfulOK, header.request_id);
+    builder.add_unsupported_requested_attributes(&unknown_keywords);
 
     {
         let printer = mx_printer.read().unwrap();
-        builder.add_required_printer_attributes(printer.deref());
+        builder.add_requested_printer_attributes(printer.deref(), &requested_attrs);
     }
 
     Ok(builder.build().unwrap())
 }
 
+// Checks the request against the printer's policy. The requesting user is the authenticated user
+// where there is one.
+fn authorize(
+    mx_printer: &Arc<RwLock<Printer>>,
+    operation: printer::Operation,
+    req: &IppRequestResponse,
+    peer: IpAddr,
+) -> Result<(), ValidationError> {
+    let user = ipprequest::requesting_user_name(req);
+    let printer = mx_printer.read().unwrap();
+    // A job which does not exist belongs to no one else, so the request is left to fail with
+    // client-error-not-found.
+    let owner = match ipprequest::job_id(req) {
+        Some(job_id) => match printer.job(job_id) {
+            Some(job) => Some(job.originating_user_name.as_str()),
+            None => user.as_deref(),
+        },
+        None => None,
+    };
+    printer
+        .policy
+        .check(operation, user.as_deref(), owner, peer)
+        .map_err(|status| {
+            println!(
+                "Denied {:?} to {} at {}",
+                operation,
+                user.as_deref().unwrap_or(ANONYMOUS_USER_NAME),
+                peer
+            );
+            ValidationError::new(status)
+        })
+}
+
+fn validation_error_response(e: ValidationError, request_id: u32) -> IppRequestResponse {
+    println!("Rejecting request: {:?}", e.status);
+    let mut builder = IppResponseBuilder::new(e.status, request_id);
+    for attr in e.unsupported {
+        builder.add_unsupported_attribute(attr);
+    }
+    builder.build().unwrap()
+}
+
 // https://tools.ietf.org/html/rfc8011#section-4.2.3
 async fn handle_validate_job(
-    _mx_printer: &Arc<RwLock<Printer>>,
+    mx_printer: &Arc<RwLock<Printer>>,
     req: &IppRequestResponse,
-) -> Result<IppRequestResponse, Infallible> {
-    let mut builder = IppResponseBuilder::new(StatusCode::SuccessfulOK, req.header().request_id);
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
+    // Return the same Operation Attributes and Unsupported Attributes as Print-Job would, but no
+    // Job Attributes.
+    let ignored = {
+        let printer = mx_printer.read().unwrap();
+        match ipprequest::validate_job_creation(&printer, req) {
+            Ok(ignored) => ignored,
+            Err(e) => return Ok(validation_error_response(e, request_id)),
+        }
+    };
 
-    // TODO: Return same Operation Attributes and Unsupported Attributes as Print-Job operation (but no Job Attributes)
-    // https://tools.ietf.org/html/rfc8011#section-4.2.3
+    let mut builder = IppResponseBuilder::new(StatusCode::SuccessfulOK, request_id);
+    for attr in ignored {
+        builder.add_unsupported_attribute(attr);
+    }
 
     Ok(builder.build().unwrap())
 }
 
+// The hold requested by the job-hold-until and job-hold-until-time Job Template attributes.
+// Unsupported values have already been reported as ignored by validate_job_creation.
+fn requested_job_hold(
+    printer: &Printer,
+    req: &IppRequestResponse,
+) -> (JobHoldUntil, Option<SystemTime>) {
+    let until =
+        ipprequest::job_hold_until(printer, ipprequest::job_attribute(req, "job-hold-until"))
+            .ok()
+            .flatten()
+            .unwrap_or(printer.job_hold_until_default);
+    let until_time =
+        ipprequest::job_hold_until_time(ipprequest::job_attribute(req, "job-hold-until-time"))
+            .ok()
+            .flatten();
+    (until, until_time)
+}
+
+fn hold_new_job(
+    printer: &mut Printer,
+    job: Job,
+    (until, until_time): (JobHoldUntil, Option<SystemTime>),
+) -> Job {
+    if until == JobHoldUntil::NoHold && until_time.is_none() {
+        return job;
+    }
+    match printer.hold_job(job.id, until, until_time) {
+        Ok(job) => job,
+        Err(e) => {
+            warn!("{}", e);
+            job
+        }
+    }
+}
+
+// Where the document data for a request comes from.
+#[derive(Copy, Clone, Debug)]
+enum DocumentSource {
+    // The data follows the attributes in the request.
+    Payload,
+    // The data is fetched from the location given in document-uri.
+    // https://tools.ietf.org/html/rfc8011#section-4.2.2
+    Reference,
+}
+
+// Reads the document data supplied with a request into the spool, decompressing it if the client
+// compressed it, and returns the file it was written to and its size.
+async fn read_document(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &mut IppRequestResponse,
+    source: DocumentSource,
+) -> Result<Result<(PathBuf, u64), ValidationError>, ServerError> {
+    let (spool, compression, max_size) = {
+        let printer = mx_printer.read().unwrap();
+        let compression = match ipprequest::compression(&printer, req) {
+            Ok(compression) => compression,
+            Err(e) => return Ok(Err(e)),
+        };
+        (
+            Arc::clone(&printer.spool),
+            compression,
+            printer.max_document_size,
+        )
+    };
+    let received = match source {
+        DocumentSource::Payload => {
+            // Note: this consumes the payload from the request. You won't be able to read it again.
+            spool
+                .receive_document(req.payload_mut(), compression, max_size)
+                .await
+        }
+        DocumentSource::Reference => match fetch_document(mx_printer, req).await {
+            Ok(document) => {
+                spool
+                    .receive_document(document.compat(), compression, max_size)
+                    .await
+            }
+            Err(e) => return Ok(Err(e)),
+        },
+    };
+    match received {
+        Ok((path, size)) => {
+            println!("Document: {} bytes", size);
+            Ok(Ok((path, size)))
+        }
+        Err(ReceiveError::TooLarge) => {
+            warn!("Document is larger than {} bytes", max_size);
+            Ok(Err(ValidationError::new(
+                StatusCode::ClientErrorRequestEntityTooLong,
+            )))
+        }
+        // https://tools.ietf.org/html/rfc8011#section-5.4.32
+        Err(ReceiveError::Compression(e)) => {
+            warn!("Document could not be decompressed: {}", e);
+            Ok(Err(ValidationError::new(
+                StatusCode::ClientErrorCompressionError,
+            )))
+        }
+        Err(ReceiveError::Io(e)) => Err(e.into()),
+    }
+}
+
+// The format a received document is printed as, settled between the document-format the client
+// sent and what the document's data looks like.
+struct DocumentFormat {
+    format: MimeMediaType,
+    detected: MimeMediaType,
+    // The document-format the client sent, if it was overridden by the detected format.
+    overridden: Option<IppAttribute>,
+}
+
+// Detects the format of a document in the spool and checks it against the format the client sent.
+// When they disagree the detected format is used if the printer attempts to override the format,
+// and the document is rejected if it does not. A document sent as application/octet-stream takes
+// the detected format, and is rejected if the printer does not support it. A rejected document is
+// discarded.
+// https://tools.ietf.org/html/rfc8011#section-5.4.28
+async fn check_document_format(
+    mx_printer: &Arc<RwLock<Printer>>,
+    path: &Path,
+    declared: MimeMediaType,
+) -> Result<Result<DocumentFormat, ValidationError>, ServerError> {
+    let spool = Arc::clone(&mx_printer.read().unwrap().spool);
+    let detected = spool.sniff(path).await?;
+
+    let checked = {
+        let printer = mx_printer.read().unwrap();
+        let supported = printer.document_format_supported.contains(&detected);
+        if declared == MimeMediaType::AutoSense {
+            // A document which was not recognised is kept as application/octet-stream, which the
+            // printer must support for the request to have got this far.
+            if supported {
+                Ok(DocumentFormat {
+                    format: detected,
+                    detected,
+                    overridden: None,
+                })
+            } else {
+                Err(ValidationError {
+                    status: StatusCode::ClientErrorDocumentFormatNotSupported,
+                    unsupported: vec![IppAttribute::new(
+                        "document-format-detected",
+                        IppValue::MimeMediaType(String::from(detected)),
+                    )],
+                })
+            }
+        } else if declared.is_compatible_with(detected) {
+            Ok(DocumentFormat {
+                format: declared,
+                detected,
+                overridden: None,
+            })
+        } else {
+            let attr = IppAttribute::new(
+                "document-format",
+                IppValue::MimeMediaType(String::from(declared)),
+            );
+            match printer.pdl_override_supported {
+                PdlOverride::Attempted if supported => Ok(DocumentFormat {
+                    format: detected,
+                    detected,
+                    overridden: Some(attr),
+                }),
+                _ => Err(ValidationError {
+                    status: StatusCode::ClientErrorDocumentFormatError,
+                    unsupported: vec![attr],
+                }),
+            }
+        }
+    };
+    if checked.is_err() {
+        warn!(
+            "Document sent as {} looks like {}",
+            String::from(declared),
+            String::from(detected)
+        );
+        spool.discard(path).await;
+    }
+    Ok(checked)
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.2.2
+async fn fetch_document(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &IppRequestResponse,
+) -> Result<Document, ValidationError> {
+    let uri = match ipprequest::operation_attribute(req, "document-uri") {
+        Some(IppValue::Uri(uri)) => uri.clone(),
+        _ => {
+            warn!("Request is missing document-uri.");
+            return Err(ValidationError {
+                status: StatusCode::ClientErrorBadRequest,
+                unsupported: Vec::new(),
+            });
+        }
+    };
+
+    let (fetchers, max_size) = {
+        let printer = mx_printer.read().unwrap();
+        (Arc::clone(&printer.fetchers), printer.max_document_size)
+    };
+    let (status, unsupported) = match fetchers.fetch(&uri, max_size).await {
+        Ok(document) => {
+            println!("Fetching {}", uri);
+            return Ok(document);
+        }
+        Err(e) => {
+            warn!("{}", e);
+            match e {
+                FetchError::UnsupportedScheme(_) => (
+                    StatusCode::ClientErrorUriSchemeNotSupported,
+                    vec![IppAttribute::new("document-uri", IppValue::Uri(uri))],
+                ),
+                FetchError::TooLarge(_) => {
+                    (StatusCode::ClientErrorRequestEntityTooLong, Vec::new())
+                }
+                FetchError::AccessError(_) => {
+                    (StatusCode::ClientErrorDocumentAccessError, Vec::new())
+                }
+            }
+        }
+    };
+    Err(ValidationError {
+        status,
+        unsupported,
+    })
+}
+
 // https://tools.ietf.org/html/rfc8011#section-4.2.1
 async fn handle_print_job(
     mx_printer: &Arc<RwLock<Printer>>,
     req: &mut IppRequestResponse,
-) -> Result<IppRequestResponse, Infallible> {
+) -> Result<IppRequestResponse, ServerError> {
+    print_job(mx_printer, req, DocumentSource::Payload).await
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.2.2
+async fn handle_print_uri(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &mut IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    print_job(mx_printer, req, DocumentSource::Reference).await
+}
+
+async fn print_job(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &mut IppRequestResponse,
+    source: DocumentSource,
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
     // Parse the request
-    // TODO: Parse all the important request attributes
+    let (ignored, format, hold) = {
+        let printer = mx_printer.read().unwrap();
+        let ignored = match ipprequest::validate_job_creation(&printer, req) {
+            Ok(ignored) => ignored,
+            Err(e) => return Ok(validation_error_response(e, request_id)),
+        };
+        let format = match ipprequest::document_format(&printer, req) {
+            Ok(format) => format,
+            Err(e) => return Ok(validation_error_response(e, request_id)),
+        };
+        (ignored, format, requested_job_hold(&printer, req))
+    };
 
-    // Read the payload in full
-    // Note: this consumes the payload from the request. You won't be able to read it again.
-    let mut data = Vec::<u8>::new();
-    async_std::io::copy(req.payload_mut(), &mut data)
-        .await
-        .unwrap();
-    println!("Payload: {} bytes", data.len());
+    let (path, size) = match read_document(mx_printer, req, source).await? {
+        Ok(document) => document,
+        Err(e) => return Ok(validation_error_response(e, request_id)),
+    };
+    let format = match check_document_format(mx_printer, &path, format).await? {
+        Ok(format) => format,
+        Err(e) => return Ok(validation_error_response(e, request_id)),
+    };
+
+    let job_name = ipprequest::job_name(req).unwrap_or_else(|| String::from(DEFAULT_JOB_NAME));
+    let user_name =
+        ipprequest::requesting_user_name(req).unwrap_or_else(|| String::from(ANONYMOUS_USER_NAME));
 
     // Create the new job
-    let job = {
+    let mut builder = IppResponseBuilder::new(StatusCode::SuccessfulOK, request_id);
+    for attr in ignored.into_iter().chain(format.overridden) {
+        builder.add_unsupported_attribute(attr);
+    }
+    {
+        let mut printer = mx_printer.write().unwrap();
+        let job = printer.new_job(
+            &job_name,
+            &user_name,
+            format.format,
+            format.detected,
+            path,
+            size,
+        );
+        let job = hold_new_job(&mut printer, job, hold);
+        println!("Created Job: {}", job.uri);
+        builder.add_required_job_attributes(&printer, &job);
+    }
+
+    Ok(builder.build().unwrap())
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.2.4
+async fn handle_create_job(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
+    let (ignored, hold) = {
+        let printer = mx_printer.read().unwrap();
+        match ipprequest::validate_job_creation(&printer, req) {
+            Ok(ignored) => (ignored, requested_job_hold(&printer, req)),
+            Err(e) => return Ok(validation_error_response(e, request_id)),
+        }
+    };
+
+    let job_name = ipprequest::job_name(req).unwrap_or_else(|| String::from(DEFAULT_JOB_NAME));
+    let user_name =
+        ipprequest::requesting_user_name(req).unwrap_or_else(|| String::from(ANONYMOUS_USER_NAME));
+
+    // Create the new job. Documents will follow in subsequent Send-Document requests.
+    let mut builder = IppResponseBuilder::new(StatusCode::SuccessfulOK, request_id);
+    for attr in ignored {
+        builder.add_unsupported_attribute(attr);
+    }
+    {
+        let mut printer = mx_printer.write().unwrap();
+        let job = printer.create_job(&job_name, &user_name);
+        let job = hold_new_job(&mut printer, job, hold);
+        println!("Created Job: {}", job.uri);
+        builder.add_required_job_attributes(&printer, &job);
+    }
+
+    Ok(builder.build().unwrap())
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.3.1
+async fn handle_send_document(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &mut IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    send_document(mx_printer, req, DocumentSource::Payload).await
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.3.2
+async fn handle_send_uri(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &mut IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    send_document(mx_printer, req, DocumentSource::Reference).await
+}
+
+async fn send_document(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &mut IppRequestResponse,
+    source: DocumentSource,
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
+    let job_id = match ipprequest::job_id(req) {
+        Some(id) => id,
+        None => {
+            warn!("Send-Document request did not identify a job.");
+            let builder = IppResponseBuilder::new(StatusCode::ClientErrorBadRequest, request_id);
+            return Ok(builder.build().unwrap());
+        }
+    };
+
+    let last_document = match ipprequest::operation_attribute(req, "last-document") {
+        Some(IppValue::Boolean(last)) => *last,
+        _ => {
+            warn!("Send-Document request is missing last-document.");
+            let builder = IppResponseBuilder::new(StatusCode::ClientErrorBadRequest, request_id);
+            return Ok(builder.build().unwrap());
+        }
+    };
+
+    let format = {
+        let printer = mx_printer.read().unwrap();
+        let validated = ipprequest::validate_operation_attributes(&printer, req)
+            .and_then(|_| ipprequest::compression(&printer, req))
+            .and_then(|_| ipprequest::document_format(&printer, req));
+        let format = match validated {
+            Ok(format) => format,
+            Err(e) => return Ok(validation_error_response(e, request_id)),
+        };
+
+        let status = match printer.job(job_id) {
+            None => Some(StatusCode::ClientErrorNotFound),
+            Some(job) if !job.is_accepting_documents() => Some(StatusCode::ClientErrorNotPossible),
+            Some(_) => None,
+        };
+        if let Some(status) = status {
+            let builder = IppResponseBuilder::new(status, request_id);
+            return Ok(builder.build().unwrap());
+        }
+        format
+    };
+
+    let (path, size) = match read_document(mx_printer, req, source).await? {
+        Ok(document) => document,
+        Err(e) => return Ok(validation_error_response(e, request_id)),
+    };
+    let format = match check_document_format(mx_printer, &path, format).await? {
+        Ok(format) => format,
+        Err(e) => return Ok(validation_error_response(e, request_id)),
+    };
+
+    // The job may have been closed by a concurrent request while we were reading the document.
+    let (job, spool) = {
+        let mut printer = mx_printer.write().unwrap();
+        let spool = Arc::clone(&printer.spool);
+        match printer.job_mut(job_id) {
+            Some(job) if job.is_accepting_documents() => {
+                job.add_document(
+                    format.format,
+                    format.detected,
+                    path.clone(),
+                    size,
+                    last_document,
+                );
+                (Some(job.clone()), spool)
+            }
+            _ => (None, spool),
+        }
+    };
+    let job = match job {
+        Some(job) => job,
+        None => {
+            spool.discard(&path).await;
+            let builder = IppResponseBuilder::new(StatusCode::ClientErrorNotPossible, request_id);
+            return Ok(builder.build().unwrap());
+        }
+    };
+
+    if let Some(document) = job.documents.last() {
+        println!(
+            "Added Document {} ({} bytes) to Job: {}",
+            document.number,
+            document.size(),
+            job.uri
+        );
+    }
+
+    let mut builder = IppResponseBuilder::new(StatusCode::SuccessfulOK, request_id);
+    if let Some(attr) = format.overridden {
+        builder.add_unsupported_attribute(attr);
+    }
+    {
+        let printer = mx_printer.read().unwrap();
+        builder.add_required_job_attributes(&printer, &job);
+    }
+
+    Ok(builder.build().unwrap())
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.3.3
+async fn handle_cancel_job(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
+    let job_id = match ipprequest::job_id(req) {
+        Some(id) => id,
+        None => {
+            warn!("Cancel-Job request did not identify a job.");
+            let builder = IppResponseBuilder::new(StatusCode::ClientErrorBadRequest, request_id);
+            return Ok(builder.build().unwrap());
+        }
+    };
+
+    let status = {
         let mut printer = mx_printer.write().unwrap();
-        printer.new_job(data.as_slice())
+        if let Err(e) = ipprequest::validate_operation_attributes(&printer, req) {
+            return Ok(validation_error_response(e, request_id));
+        }
+        match printer.job_mut(job_id) {
+            None => StatusCode::ClientErrorNotFound,
+            Some(job) => match job.cancel(JobStateReason::JobCanceledByUser) {
+                Ok(()) => {
+                    println!("Canceled Job: {}", job.uri);
+                    StatusCode::SuccessfulOK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    StatusCode::ClientErrorNotPossible
+                }
+            },
+        }
+    };
+
+    let builder = IppResponseBuilder::new(status, request_id);
+
+    Ok(builder.build().unwrap())
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.3.4
+async fn handle_get_job_attributes(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
+    let job_id = match ipprequest::job_id(req) {
+        Some(id) => id,
+        None => {
+            warn!("Get-Job-Attributes request did not identify a job.");
+            let builder = IppResponseBuilder::new(StatusCode::ClientErrorBadRequest, request_id);
+            return Ok(builder.build().unwrap());
+        }
     };
 
-    println!("Created Job: {}", job.uri);
+    // If requested-attributes is omitted the printer behaves as though "all" were supplied.
+    let requested_attr_keywords =
+        ipprequest::requested_attributes(req).unwrap_or_else(|| vec![String::from("all")]);
+    let (requested_attrs, unknown_keywords) = JobAttribute::from_keywords(&requested_attr_keywords);
 
-    let mut builder = IppResponseBuilder::new(StatusCode::SuccessfulOK, req.header().request_id);
+    let printer = mx_printer.read().unwrap();
+    if let Err(e) = ipprequest::validate_operation_attributes(&printer, req) {
+        return Ok(validation_error_response(e, request_id));
+    }
+    let job = match printer.job(job_id) {
+        Some(job) => job,
+        None => {
+            let builder = IppResponseBuilder::new(StatusCode::ClientErrorNotFound, request_id);
+            return Ok(builder.build().unwrap());
+        }
+    };
 
-    builder.add_required_job_attributes(&job);
+    let mut builder = IppResponseBuilder::new(StatusCode::SuccessfulOK, request_id);
+    builder.add_unsupported_requested_attributes(&unknown_keywords);
+    builder.add_requested_job_attributes(&printer, job, &requested_attrs);
 
     Ok(builder.build().unwrap())
 }
 
+// https://tools.ietf.org/html/rfc8011#section-4.2.6
+async fn handle_get_jobs(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
+    if let Err(e) = ipprequest::validate_operation_attributes(&mx_printer.read().unwrap(), req) {
+        return Ok(validation_error_response(e, request_id));
+    }
+
+    // Values which the printer does not support are returned in the Unsupported Attributes group.
+    let mut unsupported = Vec::<IppAttribute>::new();
+
+    // https://tools.ietf.org/html/rfc8011#section-4.2.6.1
+    let which_jobs = match ipprequest::operation_attribute(req, "which-jobs") {
+        None => None,
+        Some(IppValue::Keyword(keyword)) => match WhichJobs::try_from(keyword.as_str()) {
+            Ok(which) => Some(which),
+            Err(e) => {
+                warn!("{}", e);
+                unsupported.push(IppAttribute::new(
+                    "which-jobs",
+                    IppValue::Keyword(keyword.clone()),
+                ));
+                None
+            }
+        },
+        Some(value) => {
+            unsupported.push(IppAttribute::new("which-jobs", value.clone()));
+            None
+        }
+    };
+
+    let my_jobs = matches!(
+        ipprequest::operation_attribute(req, "my-jobs"),
+        Some(IppValue::Boolean(true))
+    );
+
+    let limit = match ipprequest::operation_attribute(req, "limit") {
+        Some(&IppValue::Integer(limit)) if limit > 0 => Some(limit as usize),
+        Some(value) => {
+            unsupported.push(IppAttribute::new("limit", value.clone()));
+            None
+        }
+        None => None,
+    };
+
+    // https://ftp.pwg.org/pub/pwg/candidates/cs-ippjobext20-20190816-5100.7.pdf
+    let first_index = match ipprequest::operation_attribute(req, "first-index") {
+        Some(&IppValue::Integer(index)) if index > 0 => index as usize,
+        Some(value) => {
+            unsupported.push(IppAttribute::new("first-index", value.clone()));
+            1
+        }
+        None => 1,
+    };
+
+    // https://ftp.pwg.org/pub/pwg/candidates/cs-ippjobext20-20190816-5100.7.pdf
+    let job_ids = match ipprequest::operation_attribute(req, "job-ids") {
+        None => None,
+        Some(value) => {
+            let values = match value {
+                IppValue::Array(values) => values.iter().collect(),
+                value => vec![value],
+            };
+            let mut ids = Vec::<u32>::new();
+            for value in values {
+                match value {
+                    &IppValue::Integer(id) if id > 0 => ids.push(id as u32),
+                    value => unsupported.push(IppAttribute::new("job-ids", value.clone())),
+                }
+            }
+            Some(ids)
+        }
+    };
+
+    if !unsupported.is_empty() {
+        let mut builder = IppResponseBuilder::new(
+            StatusCode::ClientErrorAttributesOrValuesNotSupported,
+            request_id,
+        );
+        for attr in unsupported {
+            builder.add_unsupported_attribute(attr);
+        }
+        return Ok(builder.build().unwrap());
+    }
+
+    // When specific jobs are requested, they are returned regardless of their state unless the
+    // client also asked for a particular which-jobs value.
+    let which_jobs = which_jobs.unwrap_or(match job_ids {
+        Some(_) => WhichJobs::All,
+        None => WhichJobs::default(),
+    });
+
+    let user_name = ipprequest::requesting_user_name(req);
+
+    // If requested-attributes is omitted the printer responds as though "job-uri" and "job-id"
+    // were supplied.
+    let requested_attr_keywords = ipprequest::requested_attributes(req)
+        .unwrap_or_else(|| vec![String::from("job-uri"), String::from("job-id")]);
+    let (requested_attrs, unknown_keywords) = JobAttribute::from_keywords(&requested_attr_keywords);
+
+    let printer = mx_printer.read().unwrap();
+    let jobs = printer
+        .jobs_matching(which_jobs)
+        .into_iter()
+        .filter(|job| match &job_ids {
+            Some(ids) => ids.contains(&job.id),
+            None => true,
+        })
+        .filter(|job| {
+            !my_jobs
+                || job.originating_user_name == user_name.as_deref().unwrap_or(ANONYMOUS_USER_NAME)
+        })
+        .skip(first_index - 1)
+        .take(limit.unwrap_or(usize::MAX));
+
+    let mut builder = IppResponseBuilder::new(StatusCode::SuccessfulOK, request_id);
+    builder.add_unsupported_requested_attributes(&unknown_keywords);
+    for job in jobs {
+        builder.add_job_group();
+        builder.add_requested_job_attributes(&printer, job, &requested_attrs);
+    }
+
+    Ok(builder.build().unwrap())
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.3.5
+async fn handle_hold_job(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
+    let job_id = match ipprequest::job_id(req) {
+        Some(id) => id,
+        None => {
+            warn!("Hold-Job request did not identify a job.");
+            let builder = IppResponseBuilder::new(StatusCode::ClientErrorBadRequest, request_id);
+            return Ok(builder.build().unwrap());
+        }
+    };
+
+    let status = {
+        let mut printer = mx_printer.write().unwrap();
+        // If job-hold-until is omitted the job is held until it is released.
+        let validated = ipprequest::validate_operation_attributes(&printer, req)
+            .and_then(|_| {
+                let until = ipprequest::operation_attribute(req, "job-hold-until");
+                ipprequest::job_hold_until(&printer, until)
+            })
+            .and_then(|until| {
+                let until_time = ipprequest::operation_attribute(req, "job-hold-until-time");
+                ipprequest::job_hold_until_time(until_time)
+                    .map(|until_time| (until.unwrap_or(JobHoldUntil::Indefinite), until_time))
+            });
+        let (until, until_time) = match validated {
+            Ok(hold) => hold,
+            Err(e) => return Ok(validation_error_response(e, request_id)),
+        };
+
+        match printer.job(job_id) {
+            None => StatusCode::ClientErrorNotFound,
+            Some(_) => match printer.hold_job(job_id, until, until_time) {
+                Ok(job) => {
+                    println!("Held Job: {}", job.uri);
+                    StatusCode::SuccessfulOK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    StatusCode::ClientErrorNotPossible
+                }
+            },
+        }
+    };
+
+    let builder = IppResponseBuilder::new(status, request_id);
+
+    Ok(builder.build().unwrap())
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.3.6
+async fn handle_release_job(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
+    let job_id = match ipprequest::job_id(req) {
+        Some(id) => id,
+        None => {
+            warn!("Release-Job request did not identify a job.");
+            let builder = IppResponseBuilder::new(StatusCode::ClientErrorBadRequest, request_id);
+            return Ok(builder.build().unwrap());
+        }
+    };
+
+    let status = {
+        let mut printer = mx_printer.write().unwrap();
+        if let Err(e) = ipprequest::validate_operation_attributes(&printer, req) {
+            return Ok(validation_error_response(e, request_id));
+        }
+        match printer.job_mut(job_id) {
+            None => StatusCode::ClientErrorNotFound,
+            Some(job) => match job.release() {
+                Ok(()) => {
+                    println!("Released Job: {}", job.uri);
+                    StatusCode::SuccessfulOK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    StatusCode::ClientErrorNotPossible
+                }
+            },
+        }
+    };
+
+    let builder = IppResponseBuilder::new(status, request_id);
+
+    Ok(builder.build().unwrap())
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.3.7
+async fn handle_restart_job(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
+    let job_id = match ipprequest::job_id(req) {
+        Some(id) => id,
+        None => {
+            warn!("Restart-Job request did not identify a job.");
+            let builder = IppResponseBuilder::new(StatusCode::ClientErrorBadRequest, request_id);
+            return Ok(builder.build().unwrap());
+        }
+    };
+
+    let status = {
+        let mut printer = mx_printer.write().unwrap();
+        // If job-hold-until is supplied the restarted job is held rather than queued.
+        let validated = ipprequest::validate_operation_attributes(&printer, req).and_then(|_| {
+            let until = ipprequest::operation_attribute(req, "job-hold-until");
+            ipprequest::job_hold_until(&printer, until)
+        });
+        let until = match validated {
+            Ok(until) => until,
+            Err(e) => return Ok(validation_error_response(e, request_id)),
+        };
+
+        match printer.job(job_id) {
+            None => StatusCode::ClientErrorNotFound,
+            Some(_) => match printer.restart_job(job_id, until.unwrap_or_default()) {
+                Ok(job) => {
+                    println!("Restarted Job: {}", job.uri);
+                    StatusCode::SuccessfulOK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    StatusCode::ClientErrorNotPossible
+                }
+            },
+        }
+    };
+
+    let builder = IppResponseBuilder::new(status, request_id);
+
+    Ok(builder.build().unwrap())
+}
+
+// https://tools.ietf.org/html/rfc3998#section-4.1
+async fn handle_reprocess_job(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
+    let job_id = match ipprequest::job_id(req) {
+        Some(id) => id,
+        None => {
+            warn!("Reprocess-Job request did not identify a job.");
+            let builder = IppResponseBuilder::new(StatusCode::ClientErrorBadRequest, request_id);
+            return Ok(builder.build().unwrap());
+        }
+    };
+
+    let mut printer = mx_printer.write().unwrap();
+    if let Err(e) = ipprequest::validate_operation_attributes(&printer, req) {
+        return Ok(validation_error_response(e, request_id));
+    }
+    if printer.job(job_id).is_none() {
+        let builder = IppResponseBuilder::new(StatusCode::ClientErrorNotFound, request_id);
+        return Ok(builder.build().unwrap());
+    }
+    let job = match printer.reprocess_job(job_id) {
+        Ok(job) => job,
+        Err(e) => {
+            println!("Error: {}", e);
+            let builder = IppResponseBuilder::new(StatusCode::ClientErrorNotPossible, request_id);
+            return Ok(builder.build().unwrap());
+        }
+    };
+    println!("Created Job: {} (a copy of Job {})", job.uri, job_id);
+
+    let mut builder = IppResponseBuilder::new(StatusCode::SuccessfulOK, request_id);
+    builder.add_required_job_attributes(&printer, &job);
+
+    Ok(builder.build().unwrap())
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.2.8
+async fn handle_pause_printer(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
+    {
+        let mut printer = mx_printer.write().unwrap();
+        if let Err(e) = ipprequest::validate_operation_attributes(&printer, req) {
+            return Ok(validation_error_response(e, request_id));
+        }
+        printer.pause();
+        println!("Paused Printer: {}", printer.printer_name);
+    }
+
+    let builder = IppResponseBuilder::new(StatusCode::SuccessfulOK, request_id);
+
+    Ok(builder.build().unwrap())
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.2.9
+async fn handle_resume_printer(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
+    {
+        let mut printer = mx_printer.write().unwrap();
+        if let Err(e) = ipprequest::validate_operation_attributes(&printer, req) {
+            return Ok(validation_error_response(e, request_id));
+        }
+        printer.resume();
+        println!("Resumed Printer: {}", printer.printer_name);
+    }
+
+    let builder = IppResponseBuilder::new(StatusCode::SuccessfulOK, request_id);
+
+    Ok(builder.build().unwrap())
+}
+
+// https://tools.ietf.org/html/rfc8011#section-4.2.10
+async fn handle_purge_jobs(
+    mx_printer: &Arc<RwLock<Printer>>,
+    req: &IppRequestResponse,
+) -> Result<IppRequestResponse, ServerError> {
+    let request_id = req.header().request_id;
+
+    {
+        let mut printer = mx_printer.write().unwrap();
+        if let Err(e) = ipprequest::validate_operation_attributes(&printer, req) {
+            return Ok(validation_error_response(e, request_id));
+        }
+        printer.purge_jobs();
+        println!("Purged all jobs from Printer: {}", printer.printer_name);
+    }
+
+    let builder = IppResponseBuilder::new(StatusCode::SuccessfulOK, request_id);
+
+    Ok(builder.build().unwrap())
+}
+
+const USAGE: &str = "Usage: print-srv [CONFIG-FILE]";
+
+// Exits with a message when the server cannot be started as configured.
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    std::process::exit(1);
+}
+
 #[tokio::main]
 async fn main() {
-    let printer = Printer::default();
-    let mx_printer = Arc::new(RwLock::new(printer));
+    let mut args = std::env::args().skip(1);
+    let config = match (args.next(), args.next()) {
+        (None, _) => Config::default(),
+        (Some(path), None) if !path.starts_with('-') => {
+            Config::load(Path::new(&path)).unwrap_or_else(|e| usage_error(&e))
+        }
+        _ => usage_error("print-srv takes a single configuration file"),
+    };
+    let server = config.build().unwrap_or_else(|e| usage_error(&e));
+    let listen = server.listen;
+    let mx_printer = Arc::new(RwLock::new(server.printer));
+
+    let mut listeners = Vec::new();
+    for addr in listen.ipp {
+        let mx_printer = Arc::clone(&mx_printer);
+        listeners.push(tokio::spawn(async move {
+            if let Err(e) = serve_ipp(mx_printer, addr).await {
+                eprintln!("IPP listener error on {}: {}", addr, e);
+            }
+        }));
+    }
+    if let Some(config) = server.tls_config {
+        for addr in listen.ipps {
+            let mx_printer = Arc::clone(&mx_printer);
+            let config = Arc::clone(&config);
+            listeners.push(tokio::spawn(async move {
+                if let Err(e) = tls::run(mx_printer, addr, config).await {
+                    eprintln!("IPPS listener error on {}: {}", addr, e);
+                }
+            }));
+        }
+    }
+    if let Some(addr) = listen.raw {
+        let mx_printer = Arc::clone(&mx_printer);
+        listeners.push(tokio::spawn(async move {
+            if let Err(e) = raw::run(mx_printer, addr).await {
+                eprintln!("raw listener error: {}", e);
+            }
+        }));
+    }
+    if let Some(addr) = listen.lpd {
+        let mx_printer = Arc::clone(&mx_printer);
+        listeners.push(tokio::spawn(async move {
+            if let Err(e) = lpd::run(mx_printer, addr).await {
+                eprintln!("LPD listener error: {}", e);
+            }
+        }));
+    }
+
+    // Release held jobs as their job-hold-until windows open...
+    tokio::spawn(scheduler::run(Arc::clone(&mx_printer)));
+    tokio::spawn(processor::run(Arc::clone(&mx_printer)));
+
+    // And run until every listener has stopped, or the server is told to stop...
+    tokio::select! {
+        _ = futures::future::join_all(listeners) => {}
+        _ = shutdown_signal() => println!("Shutting down"),
+    }
 
-    // Construct our SocketAddr to listen on...
-    let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
+    // Write out any changes to the jobs. A temporary spool is removed once the printer is dropped
+    // along with the runtime.
+    let spool = {
+        let mut printer = mx_printer.write().unwrap();
+        printer.sync_spool();
+        Arc::clone(&printer.spool)
+    };
+    spool.flush().await;
+}
 
+// Resolves on Ctrl-C or SIGTERM.
+async fn shutdown_signal() {
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminate) => {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+        }
+        Err(e) => {
+            warn!("Could not listen for SIGTERM: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+// Serves IPP over plain HTTP.
+// https://tools.ietf.org/html/rfc8010#section-4.2
+async fn serve_ipp(mx_printer: Arc<RwLock<Printer>>, addr: SocketAddr) -> hyper::Result<()> {
     // Create a MakeService to handle each connection...
-    let make_service = make_service_fn(move |_conn| {
+    let make_service = make_service_fn(move |conn: &AddrStream| {
         let mx_printer = Arc::clone(&mx_printer);
+        let peer = conn.remote_addr().ip();
         async move {
             Ok::<_, Infallible>(service_fn(move |req| {
                 let mx_printer = Arc::clone(&mx_printer);
-                async move { handle(&mx_printer, req).await }
+                async move { handle(&mx_printer, UriSecurityMethod::None, peer, req).await }
             }))
         }
     });
 
     // Then bind and serve...
-    let server = Server::bind(&addr).serve(make_service);
+    let server = Server::try_bind(&addr)?.serve(make_service);
+    println!("Listening for IPP requests on {}", addr);
+    server.await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::time::timeout;
+
+    use super::*;
+    use crate::printer::PrinterUri;
+
+    const PRINTER_URI: &str = "ipp://127.0.0.1/ipp/print";
+
+    // Serves a default printer on a port of its own, the way serve_ipp does.
+    fn serve() -> SocketAddr {
+        let mut printer = Printer::default();
+        printer.printer_uri_supported.push(PrinterUri::new(
+            PRINTER_URI,
+            UriAuthenticationMethod::None,
+            UriSecurityMethod::None,
+        ));
+        let mx_printer = Arc::new(RwLock::new(printer));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let make_service = make_service_fn(move |conn: &AddrStream| {
+            let mx_printer = Arc::clone(&mx_printer);
+            let peer = conn.remote_addr().ip();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let mx_printer = Arc::clone(&mx_printer);
+                    async move { handle(&mx_printer, UriSecurityMethod::None, peer, req).await }
+                }))
+            }
+        });
+        tokio::spawn(Server::from_tcp(listener).unwrap().serve(make_service));
+        addr
+    }
+
+    // Sends the header of a request which waits for 100 Continue before sending its body, and
+    // returns the status line of the first response.
+    async fn send_header(stream: &mut TcpStream, headers: &str) -> String {
+        let header = format!(
+            "POST /ipp/print HTTP/1.1\r\nHost: 127.0.0.1\r\nExpect: 100-continue\r\n{}\r\n",
+            headers
+        );
+        stream.write_all(header.as_bytes()).await.unwrap();
+        read_status_line(stream).await
+    }
+
+    // Reads a response header, returning its status line.
+    async fn read_status_line(stream: &mut TcpStream) -> String {
+        let mut response = Vec::new();
+        let mut byte = [0u8; 1];
+        timeout(Duration::from_secs(10), async {
+            while !response.ends_with(b"\r\n\r\n") {
+                if stream.read(&mut byte).await.unwrap() == 0 {
+                    break;
+                }
+                response.push(byte[0]);
+            }
+        })
+        .await
+        .expect("no response");
+        let response = String::from_utf8(response).unwrap();
+        String::from(response.lines().next().unwrap_or_default())
+    }
+
+    async fn rejected_header(headers: &str) -> String {
+        let mut stream = TcpStream::connect(serve()).await.unwrap();
+        send_header(&mut stream, headers).await
+    }
+
+    #[tokio::test]
+    async fn request_too_large_is_rejected_without_100_continue() {
+        let status =
+            rejected_header("Content-Type: application/ipp\r\nContent-Length: 1000000000000\r\n")
+                .await;
+        assert_eq!(status, "HTTP/1.1 413 Payload Too Large");
+    }
+
+    #[tokio::test]
+    async fn wrong_content_type_is_rejected_without_100_continue() {
+        let status =
+            rejected_header("Content-Type: application/pdf\r\nContent-Length: 100\r\n").await;
+        assert_eq!(status, "HTTP/1.1 415 Unsupported Media Type");
+    }
+
+    #[tokio::test]
+    async fn wrong_path_is_rejected_without_100_continue() {
+        let mut stream = TcpStream::connect(serve()).await.unwrap();
+        let header = "POST /printers/lp HTTP/1.1\r\nHost: 127.0.0.1\r\nExpect: 100-continue\r\n\
+                      Content-Type: application/ipp\r\nContent-Length: 100\r\n\r\n";
+        stream.write_all(header.as_bytes()).await.unwrap();
+        assert_eq!(
+            read_status_line(&mut stream).await,
+            "HTTP/1.1 404 Not Found"
+        );
+    }
+
+    #[tokio::test]
+    async fn accepted_header_gets_100_continue() {
+        let body = IppRequestResponse::new(
+            IppVersion::v1_1(),
+            Operation::GetPrinterAttributes,
+            Some(PRINTER_URI.parse().unwrap()),
+        )
+        .to_bytes();
+        let mut stream = TcpStream::connect(serve()).await.unwrap();
+        let status = send_header(
+            &mut stream,
+            &format!(
+                "Content-Type: application/ipp\r\nContent-Length: {}\r\n",
+                body.len()
+            ),
+        )
+        .await;
+        assert_eq!(status, "HTTP/1.1 100 Continue");
 
-    // And run forever...
-    if let Err(e) = server.await {
-        eprintln!("server error: {}", e);
+        stream.write_all(&body).await.unwrap();
+        assert_eq!(read_status_line(&mut stream).await, "HTTP/1.1 200 OK");
     }
 }
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+use ipp::proto::model::StatusCode;
+
+use crate::printer::Operation;
+
+// Members of this group may manage any job unless the policy file says otherwise.
+const OPERATORS_GROUP: &str = "operators";
+
+// Who may perform which operations, in the manner of a CUPS <Policy> block. A request is checked
+// against the first limit naming its operation; operations no limit names are open to everyone.
+// The limits of the default policy follow those of the policy file, so that a file which only
+// lists the operators still keeps jobs to their owners and the printer to its operators. Unless the
+// file names the operators, the user running the server is the only one.
+//
+// A policy file looks like this:
+//
+//     Group operators alice bob
+//
+//     <Limit job-admin>
+//     Require user @OWNER @operators
+//     </Limit>
+//
+//     <Limit printer-admin>
+//     Require user @operators
+//     Allow from 127.0.0.1 10.0.0.0/8
+//     </Limit>
+//
+// Limits name operations, such as Cancel-Job, or the groups job-admin, printer-admin, read-only and
+// All. Require user lists user names, @group names and @OWNER, the user who submitted the job.
+// Allow from lists addresses and ranges in CIDR notation, or all.
+// https://www.cups.org/doc/man-cupsd.conf.html
+#[derive(Debug)]
+pub struct Policy {
+    groups: HashMap<String, Vec<String>>,
+    limits: Vec<Limit>,
+}
+
+impl Default for Policy {
+    // Only the owner of a job or an operator may change it once it has been submitted, and only an
+    // operator may pause the printer or purge its jobs.
+    fn default() -> Self {
+        let mut groups = HashMap::new();
+        let server_user = std::env::var("USER").or_else(|_| std::env::var("LOGNAME"));
+        if let Ok(user) = server_user {
+            groups.insert(String::from(OPERATORS_GROUP), vec![user]);
+        }
+        Self {
+            groups,
+            limits: vec![
+                Limit {
+                    operations: operation_group("job-admin").unwrap(),
+                    require: vec![
+                        Principal::Owner,
+                        Principal::Group(String::from(OPERATORS_GROUP)),
+                    ],
+                    allow_from: None,
+                },
+                Limit {
+                    operations: operation_group("printer-admin").unwrap(),
+                    require: vec![Principal::Group(String::from(OPERATORS_GROUP))],
+                    allow_from: None,
+                },
+            ],
+        }
+    }
+}
+
+impl Policy {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Policy::parse(&contents).map_err(|(line, e)| format!("{}:{}: {}", path.display(), line, e))
+    }
+
+    fn parse(contents: &str) -> Result<Self, (usize, String)> {
+        let mut policy = Policy {
+            groups: Policy::default().groups,
+            limits: Vec::new(),
+        };
+        let mut limit: Option<Limit> = None;
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |e: String| (i + 1, e);
+
+            if let Some(names) = line
+                .strip_prefix("<Limit")
+                .and_then(|l| l.strip_suffix('>'))
+            {
+                if limit.is_some() {
+                    return Err(error(String::from("<Limit> blocks cannot be nested")));
+                }
+                let mut operations = Vec::new();
+                for name in names.split_whitespace() {
+                    operations.extend(operation_group(name).map_err(error)?);
+                }
+                if operations.is_empty() {
+                    return Err(error(String::from("<Limit> names no operations")));
+                }
+                limit = Some(Limit {
+                    operations,
+                    require: Vec::new(),
+                    allow_from: None,
+                });
+                continue;
+            }
+            if line == "</Limit>" {
+                match limit.take() {
+                    Some(limit) => policy.limits.push(limit),
+                    None => return Err(error(String::from("</Limit> without <Limit>"))),
+                }
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match (words.as_slice(), limit.as_mut()) {
+                (["Group", name, users @ ..], None) => {
+                    let users = users.iter().map(|&user| String::from(user)).collect();
+                    policy.groups.insert(String::from(*name), users);
+                }
+                (["Require", "user", principals @ ..], Some(limit)) => {
+                    limit
+                        .require
+                        .extend(principals.iter().map(|&p| Principal::from(p)));
+                }
+                (["Allow", "from", ranges @ ..], Some(limit)) => {
+                    let allowed = limit.allow_from.get_or_insert_with(Vec::new);
+                    for &range in ranges {
+                        if range.eq_ignore_ascii_case("all") {
+                            allowed.push(IpRange::all());
+                        } else {
+                            allowed.push(IpRange::try_from(range).map_err(error)?);
+                        }
+                    }
+                }
+                _ => return Err(error(format!("Unexpected directive: {}", line))),
+            }
+        }
+        if limit.is_some() {
+            return Err((contents.lines().count(), String::from("missing </Limit>")));
+        }
+        policy.limits.extend(Policy::default().limits);
+        Ok(policy)
+    }
+
+    // Decides whether a request may go ahead. A client at an address which is not allowed is
+    // forbidden from making the request at all; a user who is not allowed is not authorized to.
+    // https://tools.ietf.org/html/rfc8011#section-4.1.6.1
+    pub fn check(
+        &self,
+        operation: Operation,
+        user: Option<&str>,
+        job_owner: Option<&str>,
+        addr: IpAddr,
+    ) -> Result<(), StatusCode> {
+        self.check_address(operation, addr)?;
+        let limit = match self.limit(operation) {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        if limit.require.is_empty() {
+            return Ok(());
+        }
+        let user = user.ok_or(StatusCode::ClientErrorNotAuthorized)?;
+        let permitted = limit.require.iter().any(|principal| match principal {
+            Principal::Owner => job_owner == Some(user),
+            Principal::User(name) => name == user,
+            Principal::Group(group) => self
+                .groups
+                .get(group)
+                .is_some_and(|members| members.iter().any(|member| member == user)),
+        });
+        if permitted {
+            Ok(())
+        } else {
+            Err(StatusCode::ClientErrorNotAuthorized)
+        }
+    }
+
+    // Checks only where a request comes from, for listeners which turn clients away before they
+    // learn who the user is.
+    pub fn check_address(&self, operation: Operation, addr: IpAddr) -> Result<(), StatusCode> {
+        let allowed = match self
+            .limit(operation)
+            .and_then(|limit| limit.allow_from.as_ref())
+        {
+            Some(allowed) => allowed,
+            None => return Ok(()),
+        };
+        if allowed.iter().any(|range| range.contains(addr)) {
+            Ok(())
+        } else {
+            Err(StatusCode::ClientErrorForbidden)
+        }
+    }
+
+    fn limit(&self, operation: Operation) -> Option<&Limit> {
+        self.limits
+            .iter()
+            .find(|limit| limit.operations.contains(&operation))
+    }
+}
+
+#[derive(Debug)]
+struct Limit {
+    operations: Vec<Operation>,
+    // Anyone may perform the operations if this is empty.
+    require: Vec<Principal>,
+    // Requests may come from anywhere if this is None.
+    allow_from: Option<Vec<IpRange>>,
+}
+
+#[derive(Debug)]
+enum Principal {
+    // The user who submitted the job the request is for.
+    Owner,
+    User(String),
+    Group(String),
+}
+
+impl From<&str> for Principal {
+    fn from(value: &str) -> Self {
+        match value.strip_prefix('@') {
+            Some("OWNER") => Principal::Owner,
+            Some(group) => Principal::Group(String::from(group)),
+            None => Principal::User(String::from(value)),
+        }
+    }
+}
+
+// The operations a name in a <Limit> stands for.
+fn operation_group(name: &str) -> Result<Vec<Operation>, String> {
+    let operations = match name.to_ascii_lowercase().as_str() {
+        "all" => Operation::all(),
+        // Operations on jobs which have already been submitted.
+        "job-admin" => vec![
+            Operation::SendDocument,
+            Operation::SendUri,
+            Operation::CancelJob,
+            Operation::HoldJob,
+            Operation::ReleaseJob,
+            Operation::RestartJob,
+            Operation::ReprocessJob,
+        ],
+        "printer-admin" => vec![
+            Operation::PausePrinter,
+            Operation::ResumePrinter,
+            Operation::PurgeJobs,
+        ],
+        "read-only" => vec![
+            Operation::ValidateJob,
+            Operation::GetJobAttributes,
+            Operation::GetJobs,
+            Operation::GetPrinterAttributes,
+        ],
+        _ => vec![Operation::try_from(name)?],
+    };
+    Ok(operations)
+}
+
+// An address range in CIDR notation, such as 192.168.0.0/16 or fd00::/8. A single address is a
+// range of one.
+#[derive(Debug)]
+struct IpRange {
+    network: IpAddr,
+    prefix_len: u32,
+}
+
+impl IpRange {
+    fn all() -> Self {
+        Self {
+            network: IpAddr::from([0, 0, 0, 0]),
+            prefix_len: 0,
+        }
+    }
+
+    fn contains(&self, addr: IpAddr) -> bool {
+        if self.prefix_len == 0 {
+            return true;
+        }
+        // Clients of a listener bound to an IPv6 address may arrive as IPv4-mapped addresses.
+        let addr = match addr {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(addr),
+            addr => addr,
+        };
+        match (self.network, addr) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                let mask = u32::MAX << (32 - self.prefix_len);
+                u32::from(network) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                let mask = u128::MAX << (128 - self.prefix_len);
+                u128::from(network) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl TryFrom<&str> for IpRange {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let invalid = || format!("Invalid address or range {}", value);
+        let (network, prefix_len) = match value.split_once('/') {
+            Some((network, prefix_len)) => (network, Some(prefix_len)),
+            None => (value, None),
+        };
+        let network: IpAddr = network.parse().map_err(|_| invalid())?;
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len.parse().map_err(|_| invalid())?,
+            None => max_len,
+        };
+        if prefix_len > max_len {
+            return Err(invalid());
+        }
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+}
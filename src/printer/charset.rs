@@ -1,6 +1,6 @@
 use std::convert::TryFrom;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Charset {
     Utf8,
 }
@@ -1,4 +1,6 @@
-#[derive(Copy, Clone, Debug)]
+use std::convert::TryFrom;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Compression {
     None,
     Deflate,
@@ -16,3 +18,17 @@ impl From<Compression> for String {
         }
     }
 }
+
+impl TryFrom<&str> for Compression {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "none" => Ok(Compression::None),
+            "deflate" => Ok(Compression::Deflate),
+            "gzip" => Ok(Compression::GZip),
+            "compress" => Ok(Compression::Compress),
+            _ => Err(format!("Unknown Compression {}", value)),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::printer::mimemediatype::MimeMediaType;
+
+// A document's data is kept in a file in the spool rather than in memory.
+// https://tools.ietf.org/html/rfc8011#section-4.3.1
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Document {
+    pub number: u32,
+    pub format: MimeMediaType,
+    // What the document's data looked like when it was received. Documents spooled before formats
+    // were detected have none.
+    // https://ftp.pwg.org/pub/pwg/candidates/cs-ippjobext20-20190816-5100.7.pdf
+    #[serde(default)]
+    pub format_detected: Option<MimeMediaType>,
+    path: PathBuf,
+    size: u64,
+}
+
+impl Document {
+    pub fn new(
+        number: u32,
+        format: MimeMediaType,
+        format_detected: MimeMediaType,
+        path: PathBuf,
+        size: u64,
+    ) -> Self {
+        Self {
+            number,
+            format,
+            format_detected: Some(format_detected),
+            path,
+            size,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // The size of the document in octets.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
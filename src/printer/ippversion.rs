@@ -1,6 +1,7 @@
+use std::convert::TryFrom;
 use std::fmt::{Display, Formatter};
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum IppVersion {
     V1_0,
     V1_1,
@@ -9,6 +10,18 @@ pub enum IppVersion {
     V2_2,
 }
 
+impl IppVersion {
+    pub fn all() -> Vec<IppVersion> {
+        vec![
+            IppVersion::V1_0,
+            IppVersion::V1_1,
+            IppVersion::V2_0,
+            IppVersion::V2_1,
+            IppVersion::V2_2,
+        ]
+    }
+}
+
 impl From<IppVersion> for String {
     fn from(v: IppVersion) -> Self {
         match v {
@@ -39,3 +52,14 @@ impl Display for IppVersion {
         write!(f, "{}", str)
     }
 }
+
+impl TryFrom<&str> for IppVersion {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        IppVersion::all()
+            .into_iter()
+            .find(|&v| String::from(v) == value)
+            .ok_or_else(|| format!("Unknown IppVersion {}", value))
+    }
+}
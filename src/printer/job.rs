@@ -1,22 +1,257 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::printer::document::Document;
+use crate::printer::jobholduntil::JobHoldUntil;
 use crate::printer::jobstate::{JobState, JobStateReason};
+use crate::printer::mimemediatype::MimeMediaType;
+use crate::printer::timestamp::Timestamp;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct Job {
     pub id: u32,
     pub uri: String,
-    pub state: JobState,
-    pub state_reasons: Vec<JobStateReason>,
-    data: Vec<u8>,
+    pub name: String,
+    pub originating_user_name: String,
+    pub documents: Vec<Document>,
+    pub job_hold_until: JobHoldUntil,
+    pub job_hold_until_time: Option<SystemTime>,
+    state: JobState,
+    state_reasons: Vec<JobStateReason>,
+    last_document_received: bool,
+    time_at_creation: Timestamp,
+    time_at_processing: Option<Timestamp>,
+    time_at_completed: Option<Timestamp>,
 }
 
 impl Job {
-    pub fn new(id: u32, uri: String, data: &[u8]) -> Self {
+    pub fn new(id: u32, uri: String, name: &str, originating_user_name: &str) -> Self {
         Self {
             id,
             uri,
+            name: String::from(name),
+            originating_user_name: String::from(originating_user_name),
+            documents: Vec::new(),
+            job_hold_until: JobHoldUntil::NoHold,
+            job_hold_until_time: None,
             state: JobState::Pending,
-            state_reasons: vec![JobStateReason::None],
-            data: data.to_vec(),
+            state_reasons: vec![JobStateReason::JobIncoming],
+            last_document_received: false,
+            time_at_creation: Timestamp::now(),
+            time_at_processing: None,
+            time_at_completed: None,
+        }
+    }
+
+    pub fn time_at_creation(&self) -> Timestamp {
+        self.time_at_creation
+    }
+
+    pub fn time_at_processing(&self) -> Option<Timestamp> {
+        self.time_at_processing
+    }
+
+    pub fn time_at_completed(&self) -> Option<Timestamp> {
+        self.time_at_completed
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-5.3.17.1
+    pub fn k_octets(&self) -> u32 {
+        let octets: u64 = self.documents.iter().map(|d| d.size()).sum();
+        octets.div_ceil(1024) as u32
+    }
+
+    pub fn state(&self) -> JobState {
+        self.state
+    }
+
+    pub fn state_reasons(&self) -> &[JobStateReason] {
+        &self.state_reasons
+    }
+
+    pub fn is_accepting_documents(&self) -> bool {
+        !self.last_document_received && !self.state.is_terminal()
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-4.3.1
+    pub fn add_document(
+        &mut self,
+        format: MimeMediaType,
+        format_detected: MimeMediaType,
+        path: PathBuf,
+        size: u64,
+        last_document: bool,
+    ) {
+        let number = self.documents.len() as u32 + 1;
+        self.documents
+            .push(Document::new(number, format, format_detected, path, size));
+        if last_document {
+            self.close();
+        }
+    }
+
+    // Marks the job as complete so it may be processed. No further documents will be accepted.
+    pub fn close(&mut self) {
+        self.last_document_received = true;
+        self.remove_state_reason(JobStateReason::JobIncoming);
+    }
+
+    // Moves the job to a new state, rejecting any move the job state model does not allow.
+    // https://tools.ietf.org/html/rfc8011#section-5.3.7
+    pub fn transition(&mut self, next: JobState) -> Result<(), String> {
+        if !self.state.can_transition_to(next) {
+            return Err(format!(
+                "Job {} cannot move from {} to {}",
+                self.id,
+                String::from(self.state),
+                String::from(next)
+            ));
+        }
+        self.state = next;
+        if next == JobState::Processing && self.time_at_processing.is_none() {
+            self.time_at_processing = Some(Timestamp::now());
+        }
+        if next.is_terminal() {
+            self.time_at_completed = Some(Timestamp::now());
+        }
+        Ok(())
+    }
+
+    // Whether the job is waiting to be processed: it is pending and all of its documents have
+    // been received.
+    pub fn is_ready(&self) -> bool {
+        self.state == JobState::Pending && self.last_document_received
+    }
+
+    // Puts a job which was being processed when the server stopped back in the queue. The job
+    // state model has no move from processing back to pending, but the job never finished.
+    pub fn requeue(&mut self) {
+        self.state = JobState::Pending;
+        self.state_reasons = vec![JobStateReason::None];
+        self.time_at_processing = None;
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-5.3.7
+    pub fn start(&mut self) -> Result<(), String> {
+        if !self.last_document_received {
+            return Err(format!("Job {} is still receiving documents", self.id));
+        }
+        self.transition(JobState::Processing)?;
+        self.add_state_reason(JobStateReason::JobPrinting);
+        Ok(())
+    }
+
+    // Distinguishes a job waiting in the output device's own queue from one which is printing.
+    pub fn set_queued_in_device(&mut self, queued: bool) {
+        if self.state != JobState::Processing {
+            return;
+        }
+        let (from, to) = if queued {
+            (JobStateReason::JobPrinting, JobStateReason::QueuedInDevice)
+        } else {
+            (JobStateReason::QueuedInDevice, JobStateReason::JobPrinting)
+        };
+        self.remove_state_reason(from);
+        self.add_state_reason(to);
+    }
+
+    pub fn complete(&mut self) -> Result<(), String> {
+        self.transition(JobState::Completed)?;
+        self.state_reasons.clear();
+        self.add_state_reason(JobStateReason::JobCompletedSuccessfully);
+        Ok(())
+    }
+
+    pub fn abort(&mut self, reason: JobStateReason) -> Result<(), String> {
+        self.transition(JobState::Aborted)?;
+        self.state_reasons.clear();
+        self.add_state_reason(reason);
+        Ok(())
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-4.3.3
+    pub fn cancel(&mut self, reason: JobStateReason) -> Result<(), String> {
+        self.transition(JobState::Canceled)?;
+        self.last_document_received = true;
+        self.state_reasons.clear();
+        self.add_state_reason(reason);
+        Ok(())
+    }
+
+    // Returns a retained job to the queue so that it is processed again. This is the only move out
+    // of a terminal state the job state model allows.
+    // https://tools.ietf.org/html/rfc8011#section-4.3.7
+    pub fn restart(&mut self) -> Result<(), String> {
+        if !self.state.is_terminal() {
+            return Err(format!(
+                "Job {} cannot be restarted while {}",
+                self.id,
+                String::from(self.state)
+            ));
+        }
+        if self.documents.is_empty() {
+            return Err(format!("Job {} has no documents to process", self.id));
+        }
+        self.state = JobState::Pending;
+        self.state_reasons = vec![JobStateReason::None];
+        self.last_document_received = true;
+        self.time_at_processing = None;
+        self.time_at_completed = None;
+        Ok(())
+    }
+
+    // Holds the job until a time period or point in time, or indefinitely until it is released.
+    // A job which is already held has its hold condition replaced.
+    // https://tools.ietf.org/html/rfc8011#section-4.3.5
+    pub fn hold(
+        &mut self,
+        until: JobHoldUntil,
+        until_time: Option<SystemTime>,
+    ) -> Result<(), String> {
+        if self.state != JobState::PendingHeld {
+            self.transition(JobState::PendingHeld)?;
+        }
+        self.job_hold_until = until;
+        self.job_hold_until_time = until_time;
+        self.add_state_reason(JobStateReason::JobHoldUntilSpecified);
+        Ok(())
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-4.3.6
+    pub fn release(&mut self) -> Result<(), String> {
+        self.transition(JobState::Pending)?;
+        self.job_hold_until = JobHoldUntil::NoHold;
+        self.job_hold_until_time = None;
+        self.remove_state_reason(JobStateReason::JobHoldUntilSpecified);
+        Ok(())
+    }
+
+    // Whether the job's hold condition has been met. A job-hold-until-time takes precedence over
+    // the job-hold-until period.
+    pub fn is_hold_released_at(&self, t: SystemTime) -> bool {
+        match self.job_hold_until_time {
+            Some(until) => t >= until,
+            None => self
+                .job_hold_until
+                .is_released_at(&DateTime::<Local>::from(t)),
+        }
+    }
+
+    pub fn add_state_reason(&mut self, reason: JobStateReason) {
+        self.state_reasons.retain(|&r| r != JobStateReason::None);
+        if !self.state_reasons.contains(&reason) {
+            self.state_reasons.push(reason);
+        }
+    }
+
+    pub fn remove_state_reason(&mut self, reason: JobStateReason) {
+        self.state_reasons.retain(|&r| r != reason);
+        if self.state_reasons.is_empty() {
+            self.state_reasons.push(JobStateReason::None);
         }
     }
 }
@@ -0,0 +1,76 @@
+use std::convert::TryFrom;
+
+use chrono::{DateTime, Datelike, Local, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+
+// https://tools.ietf.org/html/rfc8011#section-5.2.2
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobHoldUntil {
+    DayTime,
+    Evening,
+    Indefinite,
+    Night,
+    #[default]
+    NoHold,
+    SecondShift,
+    ThirdShift,
+    Weekend,
+}
+
+impl JobHoldUntil {
+    pub fn all() -> Vec<JobHoldUntil> {
+        vec![
+            JobHoldUntil::DayTime,
+            JobHoldUntil::Evening,
+            JobHoldUntil::Indefinite,
+            JobHoldUntil::Night,
+            JobHoldUntil::NoHold,
+            JobHoldUntil::SecondShift,
+            JobHoldUntil::ThirdShift,
+            JobHoldUntil::Weekend,
+        ]
+    }
+
+    // Whether a job held with this value may be released at the given time. RFC 8011 leaves the
+    // periods to the implementation, so these follow the ones CUPS uses, in the printer's local
+    // time.
+    pub fn is_released_at(self, t: &DateTime<Local>) -> bool {
+        let hour = t.hour();
+        match self {
+            JobHoldUntil::DayTime => (6..18).contains(&hour),
+            JobHoldUntil::Evening | JobHoldUntil::Night => !(6..18).contains(&hour),
+            JobHoldUntil::Indefinite => false,
+            JobHoldUntil::NoHold => true,
+            JobHoldUntil::SecondShift => hour >= 16,
+            JobHoldUntil::ThirdShift => hour < 8,
+            JobHoldUntil::Weekend => matches!(t.weekday(), Weekday::Sat | Weekday::Sun),
+        }
+    }
+}
+
+impl From<JobHoldUntil> for String {
+    fn from(h: JobHoldUntil) -> Self {
+        match h {
+            JobHoldUntil::DayTime => String::from("day-time"),
+            JobHoldUntil::Evening => String::from("evening"),
+            JobHoldUntil::Indefinite => String::from("indefinite"),
+            JobHoldUntil::Night => String::from("night"),
+            JobHoldUntil::NoHold => String::from("no-hold"),
+            JobHoldUntil::SecondShift => String::from("second-shift"),
+            JobHoldUntil::ThirdShift => String::from("third-shift"),
+            JobHoldUntil::Weekend => String::from("weekend"),
+        }
+    }
+}
+
+impl TryFrom<&str> for JobHoldUntil {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        JobHoldUntil::all()
+            .into_iter()
+            .find(|&h| String::from(h) == value)
+            .ok_or_else(|| format!("Unknown job-hold-until value {}", value))
+    }
+}
@@ -1,5 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 // https://tools.ietf.org/html/rfc8011#section-5.3.7
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum JobState {
     Pending = 0x03,
     PendingHeld = 0x04,
@@ -10,6 +13,45 @@ pub enum JobState {
     Completed = 0x09,
 }
 
+impl JobState {
+    pub fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            JobState::Canceled | JobState::Aborted | JobState::Completed
+        )
+    }
+
+    // Legal job state transitions as described by the job state model.
+    // https://tools.ietf.org/html/rfc8011#section-5.3.7
+    pub fn can_transition_to(self, next: JobState) -> bool {
+        match self {
+            JobState::Pending => matches!(
+                next,
+                JobState::PendingHeld
+                    | JobState::Processing
+                    | JobState::Canceled
+                    | JobState::Aborted
+            ),
+            JobState::PendingHeld => matches!(
+                next,
+                JobState::Pending | JobState::Canceled | JobState::Aborted
+            ),
+            JobState::Processing => matches!(
+                next,
+                JobState::ProcessingStopped
+                    | JobState::Completed
+                    | JobState::Canceled
+                    | JobState::Aborted
+            ),
+            JobState::ProcessingStopped => matches!(
+                next,
+                JobState::Processing | JobState::Canceled | JobState::Aborted
+            ),
+            JobState::Canceled | JobState::Aborted | JobState::Completed => false,
+        }
+    }
+}
+
 impl From<JobState> for String {
     fn from(s: JobState) -> Self {
         match s {
@@ -25,10 +67,39 @@ impl From<JobState> for String {
 }
 
 // https://tools.ietf.org/html/rfc8011#section-5.3.8
-#[derive(Copy, Clone, Debug)]
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum JobStateReason {
     None,
-    // TODO: Add all values
+    JobIncoming,
+    JobDataInsufficient,
+    DocumentAccessError,
+    SubmissionInterrupted,
+    JobOutgoing,
+    JobHoldUntilSpecified,
+    ResourcesAreNotReady,
+    PrinterStoppedPartly,
+    PrinterStopped,
+    JobInterpreting,
+    JobQueued,
+    JobTransforming,
+    JobQueuedForMarker,
+    JobPrinting,
+    JobCanceledByUser,
+    JobCanceledByOperator,
+    JobCanceledAtDevice,
+    AbortedBySystem,
+    UnsupportedCompression,
+    CompressionError,
+    UnsupportedDocumentFormat,
+    DocumentFormatError,
+    ProcessingToStopPoint,
+    ServiceOffLine,
+    JobCompletedSuccessfully,
+    JobCompletedWithWarnings,
+    JobCompletedWithErrors,
+    JobRestartable,
     QueuedInDevice,
 }
 
@@ -36,6 +107,36 @@ impl From<JobStateReason> for String {
     fn from(r: JobStateReason) -> Self {
         match r {
             JobStateReason::None => String::from("none"),
+            JobStateReason::JobIncoming => String::from("job-incoming"),
+            JobStateReason::JobDataInsufficient => String::from("job-data-insufficient"),
+            JobStateReason::DocumentAccessError => String::from("document-access-error"),
+            JobStateReason::SubmissionInterrupted => String::from("submission-interrupted"),
+            JobStateReason::JobOutgoing => String::from("job-outgoing"),
+            JobStateReason::JobHoldUntilSpecified => String::from("job-hold-until-specified"),
+            JobStateReason::ResourcesAreNotReady => String::from("resources-are-not-ready"),
+            JobStateReason::PrinterStoppedPartly => String::from("printer-stopped-partly"),
+            JobStateReason::PrinterStopped => String::from("printer-stopped"),
+            JobStateReason::JobInterpreting => String::from("job-interpreting"),
+            JobStateReason::JobQueued => String::from("job-queued"),
+            JobStateReason::JobTransforming => String::from("job-transforming"),
+            JobStateReason::JobQueuedForMarker => String::from("job-queued-for-marker"),
+            JobStateReason::JobPrinting => String::from("job-printing"),
+            JobStateReason::JobCanceledByUser => String::from("job-canceled-by-user"),
+            JobStateReason::JobCanceledByOperator => String::from("job-canceled-by-operator"),
+            JobStateReason::JobCanceledAtDevice => String::from("job-canceled-at-device"),
+            JobStateReason::AbortedBySystem => String::from("aborted-by-system"),
+            JobStateReason::UnsupportedCompression => String::from("unsupported-compression"),
+            JobStateReason::CompressionError => String::from("compression-error"),
+            JobStateReason::UnsupportedDocumentFormat => {
+                String::from("unsupported-document-format")
+            }
+            JobStateReason::DocumentFormatError => String::from("document-format-error"),
+            JobStateReason::ProcessingToStopPoint => String::from("processing-to-stop-point"),
+            JobStateReason::ServiceOffLine => String::from("service-off-line"),
+            JobStateReason::JobCompletedSuccessfully => String::from("job-completed-successfully"),
+            JobStateReason::JobCompletedWithWarnings => String::from("job-completed-with-warnings"),
+            JobStateReason::JobCompletedWithErrors => String::from("job-completed-with-errors"),
+            JobStateReason::JobRestartable => String::from("job-restartable"),
             JobStateReason::QueuedInDevice => String::from("queued-in-device"),
         }
     }
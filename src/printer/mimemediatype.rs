@@ -1,4 +1,9 @@
-#[derive(Copy, Clone, Debug)]
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum MimeMediaType {
     Html,
     PlainText,
@@ -8,6 +13,12 @@ pub enum MimeMediaType {
     Postscript,
     HpPcl,
     Pdf,
+    // https://ftp.pwg.org/pub/pwg/candidates/cs-ippraster10-20120420-5102.4.pdf
+    PwgRaster,
+    // Apple Raster, as sent by AirPrint clients.
+    Urf,
+    Jpeg,
+    Png,
     AutoSense,
 }
 
@@ -22,7 +33,140 @@ impl From<MimeMediaType> for String {
             MimeMediaType::Postscript => String::from("application/postscript"),
             MimeMediaType::HpPcl => String::from("application/vnd.hp-PCL"),
             MimeMediaType::Pdf => String::from("application/pdf"),
+            MimeMediaType::PwgRaster => String::from("image/pwg-raster"),
+            MimeMediaType::Urf => String::from("image/urf"),
+            MimeMediaType::Jpeg => String::from("image/jpeg"),
+            MimeMediaType::Png => String::from("image/png"),
             MimeMediaType::AutoSense => String::from("application/octet-stream"),
         }
     }
 }
+
+impl MimeMediaType {
+    // How much of a document is looked at to guess its format.
+    pub const SNIFF_SIZE: usize = 4096;
+
+    pub fn all() -> Vec<MimeMediaType> {
+        vec![
+            MimeMediaType::Html,
+            MimeMediaType::PlainText,
+            MimeMediaType::PlainTextAscii,
+            MimeMediaType::PlainTextLatin1,
+            MimeMediaType::PlainTextUtf8,
+            MimeMediaType::Postscript,
+            MimeMediaType::HpPcl,
+            MimeMediaType::Pdf,
+            MimeMediaType::PwgRaster,
+            MimeMediaType::Urf,
+            MimeMediaType::Jpeg,
+            MimeMediaType::Png,
+            MimeMediaType::AutoSense,
+        ]
+    }
+
+    // Guesses the format of a document from its first bytes. Only the first SNIFF_SIZE octets are
+    // looked at. Returns AutoSense if the format is not recognised.
+    pub fn sniff(data: &[u8]) -> MimeMediaType {
+        let data = &data[..data.len().min(MimeMediaType::SNIFF_SIZE)];
+        if let Some(rest) = data.strip_prefix(UEL) {
+            // PJL commands may come ahead of the language they select, which is PCL unless
+            // another language can be recognised after them.
+            return match MimeMediaType::sniff(skip_pjl(rest)) {
+                MimeMediaType::PlainText | MimeMediaType::AutoSense => MimeMediaType::HpPcl,
+                format => format,
+            };
+        }
+        if data.starts_with(b"%PDF-") {
+            MimeMediaType::Pdf
+        } else if data.starts_with(b"%!") || data.starts_with(b"\x04%!") {
+            MimeMediaType::Postscript
+        } else if data.starts_with(b"\x1bE") {
+            MimeMediaType::HpPcl
+        } else if data.starts_with(b"RaS2") {
+            MimeMediaType::PwgRaster
+        } else if data.starts_with(b"UNIRAST\0") {
+            MimeMediaType::Urf
+        } else if data.starts_with(b"\xff\xd8\xff") {
+            MimeMediaType::Jpeg
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            MimeMediaType::Png
+        } else if is_text(data) {
+            MimeMediaType::PlainText
+        } else {
+            MimeMediaType::AutoSense
+        }
+    }
+
+    // Whether a document detected as the given format may be printed as this one. The text
+    // formats cannot be told apart from their first bytes, and a document which was not
+    // recognised could be anything.
+    pub fn is_compatible_with(self, detected: MimeMediaType) -> bool {
+        self == detected
+            || self == MimeMediaType::AutoSense
+            || detected == MimeMediaType::AutoSense
+            || (detected == MimeMediaType::PlainText && self.is_text())
+    }
+
+    fn is_text(self) -> bool {
+        matches!(
+            self,
+            MimeMediaType::Html
+                | MimeMediaType::PlainText
+                | MimeMediaType::PlainTextAscii
+                | MimeMediaType::PlainTextLatin1
+                | MimeMediaType::PlainTextUtf8
+        )
+    }
+}
+
+// The Universal Exit Language command, which starts a PJL job.
+// https://developers.hp.com/system/files/PJL_Technical_Reference_Manual.pdf
+const UEL: &[u8] = b"\x1b%-12345X";
+
+fn skip_pjl(mut data: &[u8]) -> &[u8] {
+    while data.starts_with(b"@PJL") {
+        match data.iter().position(|&b| b == b'\n') {
+            Some(end) => data = &data[end + 1..],
+            None => return &[],
+        }
+    }
+    data
+}
+
+// Printable UTF-8, allowing the whitespace and form feeds found in plain text print jobs. The data
+// may stop part way through a character, as only the start of a document is looked at. Nothing at
+// all could be anything, so it is not taken for text.
+fn is_text(data: &[u8]) -> bool {
+    if data.is_empty() {
+        return false;
+    }
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&data[..e.valid_up_to()]).unwrap(),
+        Err(_) => return false,
+    };
+    text.chars()
+        .all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r' | '\x0c'))
+}
+
+// Media types are matched case-insensitively and without regard to whitespace around parameters.
+// https://tools.ietf.org/html/rfc2045#section-5.1
+fn normalize(media_type: &str) -> String {
+    media_type
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+impl TryFrom<&str> for MimeMediaType {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let value = normalize(value);
+        MimeMediaType::all()
+            .into_iter()
+            .find(|&m| normalize(&String::from(m)) == value)
+            .ok_or_else(|| format!("Unknown MimeMediaType {}", value))
+    }
+}
@@ -1,26 +1,50 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use log::warn;
+
+use crate::auth::Authenticator;
+use crate::backend::{Backend, NullBackend};
+use crate::fetcher::Fetchers;
+use crate::policy::Policy;
+use crate::spool::Spool;
+
 pub use crate::printer::charset::Charset;
 pub use crate::printer::compression::Compression;
+pub use crate::printer::document::Document;
 pub use crate::printer::ippversion::IppVersion;
 pub use crate::printer::job::Job;
+pub use crate::printer::jobholduntil::JobHoldUntil;
+pub use crate::printer::jobstate::JobState;
+pub use crate::printer::jobstate::JobStateReason;
 pub use crate::printer::mimemediatype::MimeMediaType;
 pub use crate::printer::naturallanguage::NaturalLanguage;
 pub use crate::printer::operation::Operation;
 pub use crate::printer::pdloverride::PdlOverride;
 pub use crate::printer::printerstate::PrinterState;
-use crate::printer::printerstate::{PrinterStateReason, PrinterStateReasonKeyword};
-use crate::printer::uri::{PrinterUri, UriAuthenticationMethod, UriSecurityMethod};
+pub use crate::printer::printerstate::{PrinterStateReason, PrinterStateReasonKeyword};
+pub use crate::printer::timestamp::Timestamp;
+pub use crate::printer::uri::{PrinterUri, UriAuthenticationMethod, UriSecurityMethod};
+pub use crate::printer::whichjobs::WhichJobs;
 
 mod charset;
 mod compression;
+mod document;
 mod ippversion;
 mod job;
+mod jobholduntil;
 mod jobstate;
 mod mimemediatype;
 mod naturallanguage;
 mod operation;
 mod pdloverride;
 mod printerstate;
+mod timestamp;
 mod uri;
+mod whichjobs;
 
 #[derive(Debug)]
 pub struct Printer {
@@ -31,6 +55,8 @@ pub struct Printer {
     pub document_format_supported: Vec<MimeMediaType>,
     pub generated_natural_language_supported: Vec<NaturalLanguage>,
     pub ipp_versions_supported: Vec<IppVersion>,
+    pub job_hold_until_default: JobHoldUntil,
+    pub job_hold_until_supported: Vec<JobHoldUntil>,
     pub natural_language_configured: NaturalLanguage,
     pub operations_supported: Vec<Operation>,
     pub pdl_override_supported: PdlOverride,
@@ -38,10 +64,30 @@ pub struct Printer {
     pub printer_name: String,
     pub printer_state: PrinterState,
     pub printer_state_reasons: Vec<PrinterStateReason>,
-    pub printer_up_time: u32,
     pub printer_uri_supported: Vec<PrinterUri>,
+    pub which_jobs_supported: Vec<WhichJobs>,
     pub jobs: Vec<Job>,
+    // How long completed, aborted and canceled jobs, and their documents, are kept.
+    pub job_retention_period: Duration,
+    // Retrieves documents supplied by reference with Print-URI and Send-URI. There are none unless
+    // they are configured; see set_fetchers.
+    pub fetchers: Arc<Fetchers>,
+    // The largest document, in bytes, which will be accepted.
+    pub max_document_size: u64,
+    // Where the documents of processed jobs are sent.
+    pub backend: Arc<dyn Backend>,
+    // Checks the credentials of requests made to URIs which require Basic or Digest authentication.
+    pub authenticator: Option<Arc<Authenticator>>,
+    // Who may perform which operations.
+    pub policy: Policy,
+    // Where documents, and with a persistent spool jobs, are kept.
+    pub spool: Arc<Spool>,
+    // The printer-state-reasons last reported by the output device.
+    device_state_reasons: Vec<PrinterStateReason>,
+    // The jobs which have been created, changed or discarded since the spool was last synced.
+    dirty_jobs: HashSet<u32>,
     next_job_id: u32,
+    started: Timestamp,
 }
 
 impl Default for Printer {
@@ -49,13 +95,38 @@ impl Default for Printer {
         Self {
             charset_configured: Charset::Utf8,
             charset_supported: vec![Charset::Utf8],
-            compression_supported: vec![Compression::None],
+            compression_supported: vec![
+                Compression::None,
+                Compression::Deflate,
+                Compression::GZip,
+                Compression::Compress,
+            ],
             document_format_default: MimeMediaType::Pdf,
-            document_format_supported: vec![MimeMediaType::Pdf, MimeMediaType::PlainText],
+            document_format_supported: vec![
+                MimeMediaType::Pdf,
+                MimeMediaType::PlainText,
+                MimeMediaType::AutoSense,
+            ],
             generated_natural_language_supported: vec![NaturalLanguage::EN],
             ipp_versions_supported: vec![IppVersion::V1_1],
+            job_hold_until_default: JobHoldUntil::NoHold,
+            job_hold_until_supported: JobHoldUntil::all(),
             natural_language_configured: NaturalLanguage::EN,
-            operations_supported: vec![Operation::PrintJob],
+            operations_supported: vec![
+                Operation::PrintJob,
+                Operation::CreateJob,
+                Operation::SendDocument,
+                Operation::CancelJob,
+                Operation::GetJobAttributes,
+                Operation::GetJobs,
+                Operation::HoldJob,
+                Operation::ReleaseJob,
+                Operation::RestartJob,
+                Operation::PausePrinter,
+                Operation::ResumePrinter,
+                Operation::PurgeJobs,
+                Operation::ReprocessJob,
+            ],
             pdl_override_supported: PdlOverride::Attempted,
             printer_is_accepting_jobs: true,
             printer_name: String::from("Default Printer Name"),
@@ -64,32 +135,351 @@ impl Default for Printer {
                 keyword: PrinterStateReasonKeyword::None,
                 severity: None,
             }],
-            printer_up_time: 1, // TODO: Must increment every second. https://tools.ietf.org/html/rfc8011#section-5.4.29
-            printer_uri_supported: vec![PrinterUri::new(
-                "ipp://127.0.0.1:3000/ipp/print",
-                UriAuthenticationMethod::None,
-                UriSecurityMethod::None,
-            )],
+            // One for each address the server listens on; see config::Config.
+            printer_uri_supported: Vec::new(),
+            which_jobs_supported: WhichJobs::all(),
             jobs: Vec::new(),
+            job_retention_period: Duration::from_secs(7 * 24 * 60 * 60),
+            fetchers: Arc::default(),
+            max_document_size: 64 * 1024 * 1024,
+            backend: Arc::new(NullBackend),
+            authenticator: None,
+            policy: Policy::default(),
+            spool: Arc::new(Spool::temporary()),
+            device_state_reasons: Vec::new(),
+            dirty_jobs: HashSet::new(),
             next_job_id: 1,
+            started: Timestamp::now(),
         }
     }
 }
 
 impl Printer {
+    // Print-URI and Send-URI are only supported once there is somewhere documents may be fetched
+    // from.
+    // https://tools.ietf.org/html/rfc8011#section-5.4.27
+    pub fn set_fetchers(&mut self, fetchers: Fetchers) {
+        let by_reference = [Operation::PrintUri, Operation::SendUri];
+        self.operations_supported
+            .retain(|op| !by_reference.contains(op));
+        if !fetchers.schemes().is_empty() {
+            self.operations_supported.extend(by_reference);
+            self.operations_supported.sort_by_key(|&op| op as i32);
+        }
+        self.fetchers = Arc::new(fetchers);
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-5.4.29
+    pub fn printer_up_time(&self) -> u32 {
+        self.up_time_at(&Timestamp::now())
+    }
+
+    // Expresses a point in time in printer-up-time units. The printer is considered to have been
+    // up for one second when it starts.
+    pub fn up_time_at(&self, t: &Timestamp) -> u32 {
+        t.duration_since(&self.started).as_secs() as u32 + 1
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-5.4.24
     pub fn queued_job_count(&self) -> u32 {
-        // TODO: Count pending or processing jobs
-        // https://tools.ietf.org/html/rfc8011#section-5.4.24
-        return 0;
+        self.jobs
+            .iter()
+            .filter(|j| !j.state().is_terminal())
+            .count() as u32
     }
 
-    pub fn new_job(&mut self, data: &[u8]) -> Job {
+    // https://tools.ietf.org/html/rfc8011#section-5.3.15
+    pub fn intervening_job_count(&self, job: &Job) -> u32 {
+        if job.state().is_terminal() {
+            return 0;
+        }
+        self.jobs
+            .iter()
+            .filter(|j| j.id < job.id && !j.state().is_terminal())
+            .count() as u32
+    }
+
+    // Jobs matching a which-jobs filter, ordered as Get-Jobs should report them: jobs which have
+    // not completed in the order they will be processed, followed by the most recently completed.
+    // https://tools.ietf.org/html/rfc8011#section-4.2.6.2
+    pub fn jobs_matching(&self, which_jobs: WhichJobs) -> Vec<&Job> {
+        let mut jobs: Vec<&Job> = self.jobs.iter().filter(|j| which_jobs.matches(j)).collect();
+        jobs.sort_by(
+            |a, b| match (a.time_at_completed(), b.time_at_completed()) {
+                (None, None) => a.id.cmp(&b.id),
+                (None, Some(_)) => Ordering::Less,
+                (Some(_), None) => Ordering::Greater,
+                (Some(ta), Some(tb)) => tb.cmp(&ta),
+            },
+        );
+        jobs
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-4.2.4
+    pub fn create_job(&mut self, name: &str, originating_user_name: &str) -> Job {
         let job_id = self.next_job_id;
         self.next_job_id += 1;
-        let printer_uri = self.printer_uri_supported.get(0).unwrap().uri.clone();
+        let printer_uri = self.printer_uri_supported.first().unwrap().uri.clone();
         let job_uri = format!("{}/{}", printer_uri, job_id);
-        let job = Job::new(job_id, job_uri, data);
+        let job = Job::new(job_id, job_uri, name, originating_user_name);
         self.jobs.push(job.clone()); // TODO: Refactor so that we're not cloning Jobs
+        self.dirty_jobs.insert(job_id);
         job
     }
+
+    // Creates a job with a single document which has already been written to the spool.
+    pub fn new_job(
+        &mut self,
+        name: &str,
+        originating_user_name: &str,
+        format: MimeMediaType,
+        format_detected: MimeMediaType,
+        path: PathBuf,
+        size: u64,
+    ) -> Job {
+        let job_id = self.create_job(name, originating_user_name).id;
+        let job = self.job_mut(job_id).unwrap();
+        job.add_document(format, format_detected, path, size, true);
+        job.clone()
+    }
+
+    // Takes over the jobs recovered from a persistent spool. Their URIs are made again in case
+    // the printer's URIs have changed since they were created.
+    pub fn restore(&mut self, jobs: Vec<Job>, next_job_id: u32) {
+        let printer_uri = self.printer_uri_supported.first().unwrap().uri.clone();
+        self.jobs = jobs;
+        for job in &mut self.jobs {
+            job.uri = format!("{}/{}", printer_uri, job.id);
+            self.dirty_jobs.insert(job.id);
+        }
+        self.next_job_id = next_job_id;
+    }
+
+    // Hands the jobs which have changed since the last sync to the spool, which writes them, and
+    // removes the documents of discarded jobs, when it is next flushed. Call Spool::flush once the
+    // printer is unlocked.
+    pub fn sync_spool(&mut self) {
+        // next_job_id only changes when a job is created, which also marks the job.
+        if self.dirty_jobs.is_empty() {
+            return;
+        }
+        let dirty_jobs = std::mem::take(&mut self.dirty_jobs);
+        let changes = dirty_jobs
+            .into_iter()
+            .map(|job_id| (job_id, self.jobs.iter().find(|j| j.id == job_id)));
+        if let Err(e) = self.spool.stage(changes, self.next_job_id) {
+            warn!("Could not update the spool: {}", e);
+        }
+    }
+
+    // Starts processing the oldest job which is ready, unless the printer is stopped or is already
+    // processing a job. Returns a copy of the job for the processor to work from.
+    // https://tools.ietf.org/html/rfc8011#section-5.4.11
+    pub fn start_next_job(&mut self) -> Option<Job> {
+        if self.printer_state != PrinterState::Idle {
+            return None;
+        }
+        let job = self
+            .jobs
+            .iter_mut()
+            .filter(|j| j.is_ready())
+            .min_by_key(|j| j.id)?;
+        job.start().ok()?;
+        self.dirty_jobs.insert(job.id);
+        self.printer_state = PrinterState::Processing;
+        Some(job.clone())
+    }
+
+    // Records the outcome of processing a job and returns the printer to idle. A job which was
+    // canceled while it was being processed stays canceled. Returns the job if it was finished.
+    pub fn finish_job(&mut self, job_id: u32, result: Result<(), JobStateReason>) -> Option<Job> {
+        if self.printer_state == PrinterState::Processing {
+            self.printer_state = PrinterState::Idle;
+        }
+        self.set_device_state_reasons(Vec::new());
+        let job = self.job_mut(job_id)?;
+        if job.state() != JobState::Processing {
+            return None;
+        }
+        let finished = match result {
+            Ok(()) => job.complete(),
+            Err(reason) => job.abort(reason),
+        };
+        finished.ok().map(|_| job.clone())
+    }
+
+    // Returns a retained job to the queue, held if a job-hold-until value is given.
+    // https://tools.ietf.org/html/rfc8011#section-4.3.7
+    pub fn restart_job(&mut self, job_id: u32, until: JobHoldUntil) -> Result<Job, String> {
+        let job = self
+            .job_mut(job_id)
+            .ok_or_else(|| format!("Job {} does not exist", job_id))?;
+        job.restart()?;
+        if until == JobHoldUntil::NoHold {
+            return Ok(job.clone());
+        }
+        self.hold_job(job_id, until, None)
+    }
+
+    // Creates a copy of a retained job, with a new job-id, and queues it for processing.
+    // https://tools.ietf.org/html/rfc3998#section-4.1
+    pub fn reprocess_job(&mut self, job_id: u32) -> Result<Job, String> {
+        let original = self
+            .job(job_id)
+            .ok_or_else(|| format!("Job {} does not exist", job_id))?;
+        if !original.state().is_terminal() {
+            return Err(format!(
+                "Job {} cannot be reprocessed until it has completed",
+                job_id
+            ));
+        }
+        if original.documents.is_empty() {
+            return Err(format!("Job {} has no documents to process", job_id));
+        }
+        let (name, user_name) = (
+            original.name.clone(),
+            original.originating_user_name.clone(),
+        );
+        let documents = original.documents.clone();
+
+        let copy_id = self.create_job(&name, &user_name).id;
+        let job = self.job_mut(copy_id).unwrap();
+        job.documents = documents;
+        job.close();
+        Ok(job.clone())
+    }
+
+    // Discards completed, aborted and canceled jobs, along with their documents, once they have
+    // been retained for job_retention_period. Returns the discarded jobs.
+    pub fn remove_expired_jobs(&mut self, now: &Timestamp) -> Vec<Job> {
+        let retention_period = self.job_retention_period;
+        let (expired, retained) = self.jobs.drain(..).partition(|job| {
+            job.time_at_completed()
+                .map(|completed| now.duration_since(&completed) >= retention_period)
+                .unwrap_or(false)
+        });
+        self.jobs = retained;
+        self.dirty_jobs
+            .extend(expired.iter().map(|job: &Job| job.id));
+        expired
+    }
+
+    // Holds a job until its job-hold-until condition is met. A condition which has already been
+    // met releases the job again straight away.
+    // https://tools.ietf.org/html/rfc8011#section-5.2.2
+    pub fn hold_job(
+        &mut self,
+        job_id: u32,
+        until: JobHoldUntil,
+        until_time: Option<SystemTime>,
+    ) -> Result<Job, String> {
+        let job = self
+            .job_mut(job_id)
+            .ok_or_else(|| format!("Job {} does not exist", job_id))?;
+        job.hold(until, until_time)?;
+        if job.is_hold_released_at(SystemTime::now()) {
+            job.release()?;
+        }
+        Ok(job.clone())
+    }
+
+    // Releases held jobs whose job-hold-until condition has been met. Jobs held indefinitely
+    // remain held until a Release-Job request. Returns the ids of the released jobs.
+    pub fn release_held_jobs(&mut self, t: SystemTime) -> Vec<u32> {
+        let mut released = Vec::<u32>::new();
+        for job in &mut self.jobs {
+            if job.state() == JobState::PendingHeld
+                && job
+                    .state_reasons()
+                    .contains(&JobStateReason::JobHoldUntilSpecified)
+                && job.is_hold_released_at(t)
+                && job.release().is_ok()
+            {
+                released.push(job.id);
+            }
+        }
+        self.dirty_jobs.extend(&released);
+        released
+    }
+
+    // Stops the printer from processing jobs. The printer continues to accept new jobs, which are
+    // held in the queue until it is resumed.
+    // https://tools.ietf.org/html/rfc8011#section-4.2.8
+    pub fn pause(&mut self) {
+        self.printer_state = PrinterState::Stopped;
+        self.remove_state_reason(PrinterStateReasonKeyword::MovingToPaused);
+        self.add_state_reason(PrinterStateReasonKeyword::Paused.into());
+    }
+
+    // https://tools.ietf.org/html/rfc8011#section-4.2.9
+    pub fn resume(&mut self) {
+        self.remove_state_reason(PrinterStateReasonKeyword::MovingToPaused);
+        self.remove_state_reason(PrinterStateReasonKeyword::Paused);
+        // A job which was being processed when the printer was paused is allowed to finish.
+        self.printer_state = if self.jobs.iter().any(|j| j.state() == JobState::Processing) {
+            PrinterState::Processing
+        } else {
+            PrinterState::Idle
+        };
+    }
+
+    // Removes every job, regardless of state, without retaining any job history.
+    // https://tools.ietf.org/html/rfc8011#section-4.2.10
+    pub fn purge_jobs(&mut self) {
+        self.dirty_jobs
+            .extend(self.jobs.drain(..).map(|job| job.id));
+    }
+
+    // Replaces the reasons last reported by the output device, leaving the printer's own reasons,
+    // such as paused, in place.
+    pub fn set_device_state_reasons(&mut self, reasons: Vec<PrinterStateReason>) {
+        for reason in std::mem::take(&mut self.device_state_reasons) {
+            self.remove_state_reason(reason.keyword);
+        }
+        for reason in reasons {
+            if reason.keyword == PrinterStateReasonKeyword::None
+                || self
+                    .printer_state_reasons
+                    .iter()
+                    .any(|r| r.keyword == reason.keyword)
+            {
+                continue;
+            }
+            self.add_state_reason(reason);
+            self.device_state_reasons.push(reason);
+        }
+    }
+
+    fn add_state_reason(&mut self, reason: PrinterStateReason) {
+        self.printer_state_reasons
+            .retain(|r| r.keyword != PrinterStateReasonKeyword::None);
+        if !self
+            .printer_state_reasons
+            .iter()
+            .any(|r| r.keyword == reason.keyword)
+        {
+            self.printer_state_reasons.push(reason);
+        }
+    }
+
+    fn remove_state_reason(&mut self, keyword: PrinterStateReasonKeyword) {
+        self.printer_state_reasons.retain(|r| r.keyword != keyword);
+        if self.printer_state_reasons.is_empty() {
+            self.printer_state_reasons.push(PrinterStateReason {
+                keyword: PrinterStateReasonKeyword::None,
+                severity: None,
+            });
+        }
+    }
+
+    pub fn job(&self, job_id: u32) -> Option<&Job> {
+        self.jobs.iter().find(|j| j.id == job_id)
+    }
+
+    // The job is assumed to change, so it is written to the spool at the next sync.
+    pub fn job_mut(&mut self, job_id: u32) -> Option<&mut Job> {
+        let job = self.jobs.iter_mut().find(|j| j.id == job_id)?;
+        self.dirty_jobs.insert(job_id);
+        Some(job)
+    }
 }
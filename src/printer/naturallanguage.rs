@@ -1,8 +1,16 @@
+use std::convert::TryFrom;
+
 #[derive(Copy, Clone, Debug)]
 pub enum NaturalLanguage {
     EN,
 }
 
+impl NaturalLanguage {
+    pub fn all() -> Vec<NaturalLanguage> {
+        vec![NaturalLanguage::EN]
+    }
+}
+
 impl From<NaturalLanguage> for String {
     fn from(lang: NaturalLanguage) -> Self {
         match lang {
@@ -10,3 +18,16 @@ impl From<NaturalLanguage> for String {
         }
     }
 }
+
+// Language tags are case-insensitive.
+// https://tools.ietf.org/html/rfc5646#section-2.1.1
+impl TryFrom<&str> for NaturalLanguage {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        NaturalLanguage::all()
+            .into_iter()
+            .find(|&lang| String::from(lang).eq_ignore_ascii_case(value))
+            .ok_or_else(|| format!("Unsupported NaturalLanguage {}", value))
+    }
+}
@@ -1,4 +1,7 @@
-#[derive(Copy, Clone, Debug)]
+use std::convert::TryFrom;
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Operation {
     PrintJob = 0x0002,
     PrintUri = 0x0003,
@@ -16,4 +19,78 @@ pub enum Operation {
     PausePrinter = 0x0010,
     ResumePrinter = 0x0011,
     PurgeJobs = 0x0012,
+    // https://tools.ietf.org/html/rfc3998#section-4.1
+    ReprocessJob = 0x002C,
+}
+
+impl Operation {
+    pub fn all() -> Vec<Operation> {
+        vec![
+            Operation::PrintJob,
+            Operation::PrintUri,
+            Operation::ValidateJob,
+            Operation::CreateJob,
+            Operation::SendDocument,
+            Operation::SendUri,
+            Operation::CancelJob,
+            Operation::GetJobAttributes,
+            Operation::GetJobs,
+            Operation::GetPrinterAttributes,
+            Operation::HoldJob,
+            Operation::ReleaseJob,
+            Operation::RestartJob,
+            Operation::PausePrinter,
+            Operation::ResumePrinter,
+            Operation::PurgeJobs,
+            Operation::ReprocessJob,
+        ]
+    }
+}
+
+impl TryFrom<u16> for Operation {
+    type Error = String;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        Operation::all()
+            .into_iter()
+            .find(|&op| op as u16 == value)
+            .ok_or_else(|| format!("Unknown operation-id {:#06x}", value))
+    }
+}
+
+// https://tools.ietf.org/html/rfc8011#section-5.4.15
+impl From<Operation> for String {
+    fn from(op: Operation) -> Self {
+        match op {
+            Operation::PrintJob => String::from("Print-Job"),
+            Operation::PrintUri => String::from("Print-URI"),
+            Operation::ValidateJob => String::from("Validate-Job"),
+            Operation::CreateJob => String::from("Create-Job"),
+            Operation::SendDocument => String::from("Send-Document"),
+            Operation::SendUri => String::from("Send-URI"),
+            Operation::CancelJob => String::from("Cancel-Job"),
+            Operation::GetJobAttributes => String::from("Get-Job-Attributes"),
+            Operation::GetJobs => String::from("Get-Jobs"),
+            Operation::GetPrinterAttributes => String::from("Get-Printer-Attributes"),
+            Operation::HoldJob => String::from("Hold-Job"),
+            Operation::ReleaseJob => String::from("Release-Job"),
+            Operation::RestartJob => String::from("Restart-Job"),
+            Operation::PausePrinter => String::from("Pause-Printer"),
+            Operation::ResumePrinter => String::from("Resume-Printer"),
+            Operation::PurgeJobs => String::from("Purge-Jobs"),
+            Operation::ReprocessJob => String::from("Reprocess-Job"),
+        }
+    }
+}
+
+// Operation names are matched case-insensitively, as CUPS does in its policies.
+impl TryFrom<&str> for Operation {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Operation::all()
+            .into_iter()
+            .find(|&op| String::from(op).eq_ignore_ascii_case(value))
+            .ok_or_else(|| format!("Unknown operation {}", value))
+    }
 }
@@ -1,3 +1,5 @@
+use std::convert::TryFrom;
+
 #[derive(Copy, Clone, Debug)]
 pub enum PdlOverride {
     Attempted,
@@ -12,3 +14,15 @@ impl From<PdlOverride> for String {
         }
     }
 }
+
+impl TryFrom<&str> for PdlOverride {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "attempted" => Ok(PdlOverride::Attempted),
+            "not-attempted" => Ok(PdlOverride::NotAttempted),
+            _ => Err(format!("Unknown PdlOverride {}", value)),
+        }
+    }
+}
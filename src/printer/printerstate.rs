@@ -1,16 +1,27 @@
-#[derive(Copy, Clone, Debug)]
+use std::convert::TryFrom;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum PrinterState {
     Idle = 0x03,
     Processing = 0x04,
     Stopped = 0x05,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct PrinterStateReason {
     pub keyword: PrinterStateReasonKeyword,
     pub severity: Option<PrinterStateReasonSeverity>,
 }
 
+impl From<PrinterStateReasonKeyword> for PrinterStateReason {
+    fn from(keyword: PrinterStateReasonKeyword) -> Self {
+        Self {
+            keyword,
+            severity: None,
+        }
+    }
+}
+
 impl From<PrinterStateReason> for String {
     fn from(reason: PrinterStateReason) -> Self {
         let severity = match reason.severity {
@@ -21,13 +32,44 @@ impl From<PrinterStateReason> for String {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+// A keyword may carry a "-report", "-warning" or "-error" severity suffix.
+// https://tools.ietf.org/html/rfc8011#section-5.4.12
+impl TryFrom<&str> for PrinterStateReason {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        for severity in PrinterStateReasonSeverity::all() {
+            let suffix = format!("-{}", String::from(severity));
+            if let Some(keyword) = value.strip_suffix(suffix.as_str()) {
+                if let Ok(keyword) = PrinterStateReasonKeyword::try_from(keyword) {
+                    return Ok(Self {
+                        keyword,
+                        severity: Some(severity),
+                    });
+                }
+            }
+        }
+        PrinterStateReasonKeyword::try_from(value).map(PrinterStateReason::from)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum PrinterStateReasonSeverity {
     Report,
     Warning,
     Error,
 }
 
+impl PrinterStateReasonSeverity {
+    pub fn all() -> Vec<PrinterStateReasonSeverity> {
+        vec![
+            PrinterStateReasonSeverity::Report,
+            PrinterStateReasonSeverity::Warning,
+            PrinterStateReasonSeverity::Error,
+        ]
+    }
+}
+
 impl From<PrinterStateReasonSeverity> for String {
     fn from(sev: PrinterStateReasonSeverity) -> Self {
         match sev {
@@ -38,7 +80,7 @@ impl From<PrinterStateReasonSeverity> for String {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum PrinterStateReasonKeyword {
     None,
     Other,
@@ -76,6 +118,47 @@ pub enum PrinterStateReasonKeyword {
     TonerLow,
 }
 
+impl PrinterStateReasonKeyword {
+    pub fn all() -> Vec<PrinterStateReasonKeyword> {
+        vec![
+            PrinterStateReasonKeyword::None,
+            PrinterStateReasonKeyword::Other,
+            PrinterStateReasonKeyword::ConnectingToDevice,
+            PrinterStateReasonKeyword::CoverOpen,
+            PrinterStateReasonKeyword::DeveloperEmpty,
+            PrinterStateReasonKeyword::DeveloperLow,
+            PrinterStateReasonKeyword::DoorOpen,
+            PrinterStateReasonKeyword::FuserOverTemp,
+            PrinterStateReasonKeyword::FuserUnderTemp,
+            PrinterStateReasonKeyword::InputTrayMissing,
+            PrinterStateReasonKeyword::InterlockOpen,
+            PrinterStateReasonKeyword::InterpreterResourceUnavailable,
+            PrinterStateReasonKeyword::MarkerSupplyEmpty,
+            PrinterStateReasonKeyword::MarkerSupplyLow,
+            PrinterStateReasonKeyword::MarkerWasteAlmostFull,
+            PrinterStateReasonKeyword::MarkerWasteFull,
+            PrinterStateReasonKeyword::MediaEmpty,
+            PrinterStateReasonKeyword::MediaJam,
+            PrinterStateReasonKeyword::MediaLow,
+            PrinterStateReasonKeyword::MediaNeeded,
+            PrinterStateReasonKeyword::MovingToPaused,
+            PrinterStateReasonKeyword::OpcLifeOver,
+            PrinterStateReasonKeyword::OpcNearEol,
+            PrinterStateReasonKeyword::OutputAreaAlmostFull,
+            PrinterStateReasonKeyword::OutputAreaFull,
+            PrinterStateReasonKeyword::OutputTrayMissing,
+            PrinterStateReasonKeyword::Paused,
+            PrinterStateReasonKeyword::Shutdown,
+            PrinterStateReasonKeyword::SpoolAreaFull,
+            PrinterStateReasonKeyword::StoppedPartly,
+            PrinterStateReasonKeyword::Stopping,
+            PrinterStateReasonKeyword::TimedOut,
+            PrinterStateReasonKeyword::TonerEmpty,
+            PrinterStateReasonKeyword::TonerLow,
+        ]
+    }
+}
+
 impl From<PrinterStateReasonKeyword> for String {
     fn from(kw: PrinterStateReasonKeyword) -> Self {
         match kw {
@@ -122,3 +205,14 @@ impl From<PrinterStateReasonKeyword> for String {
         }
     }
 }
+
+impl TryFrom<&str> for PrinterStateReasonKeyword {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        PrinterStateReasonKeyword::all()
+            .into_iter()
+            .find(|&kw| String::from(kw) == value)
+            .ok_or_else(|| format!("Unknown printer-state-reasons keyword {}", value))
+    }
+}
@@ -0,0 +1,68 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+// A point in time which can be expressed both relative to printer-up-time and as a date-time.
+// Only the date-time is saved, as instants do not survive a restart.
+// https://tools.ietf.org/html/rfc8011#section-5.3.14
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "SystemTime", into = "SystemTime")]
+pub struct Timestamp {
+    instant: Instant,
+    system_time: SystemTime,
+}
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self {
+            instant: Instant::now(),
+            system_time: SystemTime::now(),
+        }
+    }
+
+    pub fn system_time(&self) -> SystemTime {
+        self.system_time
+    }
+
+    pub fn duration_since(&self, earlier: &Timestamp) -> Duration {
+        self.instant.saturating_duration_since(earlier.instant)
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    // Places a saved date-time on the monotonic clock. Times from before the clock started, such
+    // as those from before a reboot, are placed at its earliest point.
+    fn from(system_time: SystemTime) -> Self {
+        let now = Timestamp::now();
+        let elapsed = now
+            .system_time
+            .duration_since(system_time)
+            .unwrap_or_default();
+        Self {
+            instant: now
+                .instant
+                .checked_sub(elapsed)
+                .unwrap_or_else(|| earliest_instant(now.instant)),
+            system_time,
+        }
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(t: Timestamp) -> Self {
+        t.system_time
+    }
+}
+
+fn earliest_instant(now: Instant) -> Instant {
+    let mut earliest = now;
+    let mut step = Duration::from_secs(1 << 32);
+    while step > Duration::ZERO {
+        if let Some(earlier) = earliest.checked_sub(step) {
+            earliest = earlier;
+        } else {
+            step /= 2;
+        }
+    }
+    earliest
+}
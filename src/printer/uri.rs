@@ -42,7 +42,7 @@ impl From<UriAuthenticationMethod> for String {
 }
 
 // https://tools.ietf.org/html/rfc8011#section-5.4.3
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum UriSecurityMethod {
     None,
     Tls,
@@ -0,0 +1,84 @@
+use std::convert::TryFrom;
+
+use crate::printer::job::Job;
+use crate::printer::jobstate::JobState;
+
+// https://tools.ietf.org/html/rfc8011#section-4.2.6.1
+// https://ftp.pwg.org/pub/pwg/candidates/cs-ippjobext20-20190816-5100.7.pdf
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum WhichJobs {
+    Aborted,
+    All,
+    Canceled,
+    Completed,
+    Fetchable,
+    #[default]
+    NotCompleted,
+    Pending,
+    PendingHeld,
+    Processing,
+    ProcessingStopped,
+}
+
+impl WhichJobs {
+    pub fn all() -> Vec<WhichJobs> {
+        vec![
+            WhichJobs::Aborted,
+            WhichJobs::All,
+            WhichJobs::Canceled,
+            WhichJobs::Completed,
+            WhichJobs::Fetchable,
+            WhichJobs::NotCompleted,
+            WhichJobs::Pending,
+            WhichJobs::PendingHeld,
+            WhichJobs::Processing,
+            WhichJobs::ProcessingStopped,
+        ]
+    }
+
+    pub fn matches(self, job: &Job) -> bool {
+        let state = job.state();
+        match self {
+            WhichJobs::Aborted => state == JobState::Aborted,
+            WhichJobs::All => true,
+            WhichJobs::Canceled => state == JobState::Canceled,
+            WhichJobs::Completed => state.is_terminal(),
+            // We have no separate fetch queue, so any pending job which has received all of its
+            // documents is ready to be fetched.
+            WhichJobs::Fetchable => state == JobState::Pending && !job.is_accepting_documents(),
+            WhichJobs::NotCompleted => !state.is_terminal(),
+            WhichJobs::Pending => state == JobState::Pending,
+            WhichJobs::PendingHeld => state == JobState::PendingHeld,
+            WhichJobs::Processing => state == JobState::Processing,
+            WhichJobs::ProcessingStopped => state == JobState::ProcessingStopped,
+        }
+    }
+}
+
+impl From<WhichJobs> for String {
+    fn from(w: WhichJobs) -> Self {
+        match w {
+            WhichJobs::Aborted => String::from("aborted"),
+            WhichJobs::All => String::from("all"),
+            WhichJobs::Canceled => String::from("canceled"),
+            WhichJobs::Completed => String::from("completed"),
+            WhichJobs::Fetchable => String::from("fetchable"),
+            WhichJobs::NotCompleted => String::from("not-completed"),
+            WhichJobs::Pending => String::from("pending"),
+            WhichJobs::PendingHeld => String::from("pending-held"),
+            WhichJobs::Processing => String::from("processing"),
+            WhichJobs::ProcessingStopped => String::from("processing-stopped"),
+        }
+    }
+}
+
+impl TryFrom<&str> for WhichJobs {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        WhichJobs::all()
+            .into_iter()
+            .find(|&w| String::from(w) == value)
+            .ok_or_else(|| format!("Unknown which-jobs value {}", value))
+    }
+}
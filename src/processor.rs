@@ -0,0 +1,224 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use log::warn;
+
+use crate::backend::DeviceStatus;
+use crate::printer::Printer;
+
+// How long the processor waits before looking for work again when no job is ready.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Processes jobs one at a time, in the order they were submitted, moving each through processing
+// to completed or aborted.
+// https://tools.ietf.org/html/rfc8011#section-5.3.7
+pub async fn run(mx_printer: Arc<RwLock<Printer>>) {
+    loop {
+        let (job, backend, spool) = {
+            let mut printer = mx_printer.write().unwrap();
+            let job = printer.start_next_job();
+            printer.sync_spool();
+            (
+                job,
+                Arc::clone(&printer.backend),
+                Arc::clone(&printer.spool),
+            )
+        };
+        spool.flush().await;
+        let job = match job {
+            Some(job) => job,
+            None => {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                continue;
+            }
+        };
+        println!("Processing Job: {}", job.uri);
+
+        let status = DeviceStatus::new(Arc::clone(&mx_printer), job.id);
+        let result = backend.print(&job, &status).await.map_err(|e| {
+            warn!("Job {} failed: {}", job.uri, e);
+            e.job_state_reason()
+        });
+
+        let finished = {
+            let mut printer = mx_printer.write().unwrap();
+            let finished = printer.finish_job(job.id, result);
+            printer.sync_spool();
+            finished
+        };
+        spool.flush().await;
+        match finished {
+            Some(job) => println!("Finished Job: {} ({})", job.uri, String::from(job.state())),
+            None => println!("Job {} was stopped while processing", job.uri),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tokio::time::{sleep, timeout};
+
+    use super::*;
+    use crate::backend::{Backend, CommandBackend, DirectoryBackend, NullBackend};
+    use crate::printer::{
+        JobState, JobStateReason, MimeMediaType, PrinterState, PrinterUri, UriAuthenticationMethod,
+        UriSecurityMethod,
+    };
+    use crate::spool::Spool;
+
+    // A printer with a job for the given document, kept in a spool of its own.
+    fn printer(backend: Arc<dyn Backend>, spool: &Path, document: &[u8]) -> Arc<RwLock<Printer>> {
+        let mut printer = Printer::default();
+        printer.backend = backend;
+        printer.spool = Arc::new(Spool::open(spool).unwrap());
+        printer.printer_uri_supported.push(PrinterUri::new(
+            "ipp://localhost/ipp/print",
+            UriAuthenticationMethod::None,
+            UriSecurityMethod::None,
+        ));
+        let path = spool.join("d00000001");
+        std::fs::write(&path, document).unwrap();
+        printer.new_job(
+            "test",
+            "alice",
+            MimeMediaType::PlainText,
+            MimeMediaType::PlainText,
+            path,
+            document.len() as u64,
+        );
+        Arc::new(RwLock::new(printer))
+    }
+
+    // Waits for the printer's only job to reach the given state, returning the reasons for it once
+    // the printer is idle again.
+    async fn wait_for(mx_printer: &Arc<RwLock<Printer>>, state: JobState) -> Vec<JobStateReason> {
+        let waited = timeout(Duration::from_secs(10), async {
+            loop {
+                {
+                    let printer = mx_printer.read().unwrap();
+                    let job = printer.job(1).unwrap();
+                    if job.state() == state && printer.printer_state == PrinterState::Idle {
+                        return job.state_reasons().to_vec();
+                    }
+                    if job.state().is_terminal() && job.state() != state {
+                        panic!("job was {:?}", job.state());
+                    }
+                }
+                sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await;
+        waited.unwrap_or_else(|_| panic!("job did not become {:?}", state))
+    }
+
+    // Runs the processor until the printer's only job has reached the given state.
+    async fn process(mx_printer: &Arc<RwLock<Printer>>, state: JobState) -> Vec<JobStateReason> {
+        let processor = tokio::spawn(run(Arc::clone(mx_printer)));
+        let reasons = wait_for(mx_printer, state).await;
+        processor.abort();
+        reasons
+    }
+
+    #[tokio::test]
+    async fn null_backend_completes_job() {
+        let spool = tempfile::tempdir().unwrap();
+        let mx_printer = printer(Arc::new(NullBackend), spool.path(), b"hello\n");
+        let reasons = process(&mx_printer, JobState::Completed).await;
+        assert_eq!(reasons, vec![JobStateReason::JobCompletedSuccessfully]);
+    }
+
+    #[tokio::test]
+    async fn directory_backend_writes_document_and_sidecar() {
+        let spool = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let backend = Arc::new(DirectoryBackend::new(output.path().join("out")));
+        let mx_printer = printer(backend, spool.path(), b"hello\n");
+        process(&mx_printer, JobState::Completed).await;
+
+        let document = output.path().join("out/job-1-document-1.txt");
+        assert_eq!(std::fs::read(document).unwrap(), b"hello\n");
+        let sidecar = std::fs::read(output.path().join("out/job-1-document-1.json")).unwrap();
+        let sidecar: serde_json::Value = serde_json::from_slice(&sidecar).unwrap();
+        assert_eq!(sidecar["job-id"], 1);
+        assert_eq!(sidecar["job-originating-user-name"], "alice");
+        assert_eq!(sidecar["document-format"], "text/plain");
+        assert_eq!(sidecar["document-octets"], 6);
+    }
+
+    #[tokio::test]
+    async fn directory_backend_aborts_job_it_cannot_write() {
+        let spool = tempfile::tempdir().unwrap();
+        let output = tempfile::NamedTempFile::new().unwrap();
+        // The output directory would have to be made inside a file.
+        let backend = Arc::new(DirectoryBackend::new(output.path().join("out")));
+        let mx_printer = printer(backend, spool.path(), b"hello\n");
+        let reasons = process(&mx_printer, JobState::Aborted).await;
+        assert_eq!(reasons, vec![JobStateReason::ServiceOffLine]);
+    }
+
+    // The shell runs the document it is given as a script, with the job's attributes in its
+    // environment.
+    #[tokio::test]
+    async fn command_backend_runs_command_with_job_attributes() {
+        let spool = tempfile::tempdir().unwrap();
+        let output = spool.path().join("out");
+        let script = format!(
+            "echo \"$IPP_JOB_ID $IPP_JOB_ORIGINATING_USER_NAME $IPP_DOCUMENT_FORMAT\" > {}\n",
+            output.display()
+        );
+        let backend = Arc::new(CommandBackend::new("/bin/sh"));
+        let mx_printer = printer(backend, spool.path(), script.as_bytes());
+        process(&mx_printer, JobState::Completed).await;
+        assert_eq!(
+            std::fs::read_to_string(output).unwrap(),
+            "1 alice text/plain\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn command_backend_aborts_job_when_command_fails() {
+        let spool = tempfile::tempdir().unwrap();
+        let backend = Arc::new(CommandBackend::new("/bin/sh"));
+        let mx_printer = printer(backend, spool.path(), b"exit 3\n");
+        let reasons = process(&mx_printer, JobState::Aborted).await;
+        assert_eq!(reasons, vec![JobStateReason::AbortedBySystem]);
+    }
+
+    #[tokio::test]
+    async fn command_backend_aborts_job_when_command_is_missing() {
+        let spool = tempfile::tempdir().unwrap();
+        let backend = Arc::new(CommandBackend::new("/nonexistent/command"));
+        let mx_printer = printer(backend, spool.path(), b"hello\n");
+        let reasons = process(&mx_printer, JobState::Aborted).await;
+        assert_eq!(reasons, vec![JobStateReason::ServiceOffLine]);
+    }
+
+    #[tokio::test]
+    async fn command_backend_stops_when_job_is_canceled() {
+        let spool = tempfile::tempdir().unwrap();
+        let backend = Arc::new(CommandBackend::new("/bin/sh"));
+        let mx_printer = printer(backend, spool.path(), b"exec sleep 60\n");
+        let processor = tokio::spawn(run(Arc::clone(&mx_printer)));
+        timeout(Duration::from_secs(10), async {
+            while mx_printer.read().unwrap().job(1).unwrap().state() != JobState::Processing {
+                sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("job was not started");
+        mx_printer
+            .write()
+            .unwrap()
+            .job_mut(1)
+            .unwrap()
+            .cancel(JobStateReason::JobCanceledByUser)
+            .unwrap();
+
+        // The printer only becomes idle again once the command has been killed.
+        let reasons = wait_for(&mx_printer, JobState::Canceled).await;
+        processor.abort();
+        assert_eq!(reasons, vec![JobStateReason::JobCanceledByUser]);
+    }
+}
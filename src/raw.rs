@@ -0,0 +1,129 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use log::warn;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+use crate::printer::{Compression, Operation, Printer};
+use crate::spool::ReceiveError;
+use crate::timeout::IdleTimeout;
+use crate::{ANONYMOUS_USER_NAME, DEFAULT_JOB_NAME};
+
+// How long a client may stay silent before its connection is dropped.
+const READ_TIMEOUT: Duration = Duration::from_secs(300);
+// How long to wait before accepting connections again after accepting one failed, e.g. because
+// the process ran out of file descriptors.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+// Accepts documents sent to an AppSocket (JetDirect) style port, for clients which cannot speak
+// IPP. Everything received on a connection, up to the point the client closes it, becomes one job.
+pub async fn run(mx_printer: Arc<RwLock<Printer>>, addr: SocketAddr) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    println!("Listening for raw print jobs on {}", addr);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Could not accept a raw print connection: {}", e);
+                tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                continue;
+            }
+        };
+        let mx_printer = Arc::clone(&mx_printer);
+        tokio::spawn(async move {
+            if let Err(e) = receive(&mx_printer, stream, peer).await {
+                warn!("Raw print job from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+// Raw jobs have no user, so they are only accepted where the policy lets anyone print.
+async fn receive(
+    mx_printer: &Arc<RwLock<Printer>>,
+    stream: TcpStream,
+    peer: SocketAddr,
+) -> std::io::Result<()> {
+    let (spool, max_size) = {
+        let printer = mx_printer.read().unwrap();
+        if let Err(status) = printer
+            .policy
+            .check(Operation::PrintJob, None, None, peer.ip())
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("denied by policy: {:?}", status),
+            ));
+        }
+        (Arc::clone(&printer.spool), printer.max_document_size)
+    };
+
+    let reader = IdleTimeout::new(stream, READ_TIMEOUT).compat();
+    let (path, size) = match spool
+        .receive_document(reader, Compression::None, max_size)
+        .await
+    {
+        Ok(document) => document,
+        Err(ReceiveError::Io(e)) | Err(ReceiveError::Compression(e)) => return Err(e),
+        Err(ReceiveError::TooLarge) => {
+            return Err(std::io::Error::other(format!(
+                "document is larger than {} bytes",
+                max_size
+            )))
+        }
+    };
+    if size == 0 {
+        spool.discard(&path).await;
+        return Ok(());
+    }
+
+    let format = match spool.sniff(&path).await {
+        Ok(format) => format,
+        Err(e) => {
+            spool.discard(&path).await;
+            return Err(e);
+        }
+    };
+
+    let job = {
+        let mut printer = mx_printer.write().unwrap();
+        if !printer.document_format_supported.contains(&format) {
+            Err(format!(
+                "document format {} is not supported",
+                String::from(format)
+            ))
+        } else if printer.printer_is_accepting_jobs {
+            let job = printer.new_job(
+                DEFAULT_JOB_NAME,
+                ANONYMOUS_USER_NAME,
+                format,
+                format,
+                path.clone(),
+                size,
+            );
+            printer.sync_spool();
+            Ok(job)
+        } else {
+            Err("printer is not accepting jobs".to_string())
+        }
+    };
+    let job = match job {
+        Ok(job) => {
+            spool.flush().await;
+            job
+        }
+        Err(reason) => {
+            spool.discard(&path).await;
+            return Err(std::io::Error::other(reason));
+        }
+    };
+    println!(
+        "Created Job: {} ({}, {} bytes)",
+        job.uri,
+        String::from(format),
+        size
+    );
+    Ok(())
+}
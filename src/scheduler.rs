@@ -0,0 +1,31 @@
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use crate::printer::{Printer, Timestamp};
+
+// How often the scheduler checks held and retained jobs.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// Releases held jobs once their job-hold-until condition is met, and discards retained jobs once
+// their retention period has passed.
+// https://tools.ietf.org/html/rfc8011#section-5.2.2
+pub async fn run(mx_printer: Arc<RwLock<Printer>>) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let spool = {
+            let mut printer = mx_printer.write().unwrap();
+            for job_id in printer.release_held_jobs(SystemTime::now()) {
+                if let Some(job) = printer.job(job_id) {
+                    println!("Released Job: {}", job.uri);
+                }
+            }
+            for job in printer.remove_expired_jobs(&Timestamp::now()) {
+                println!("Discarded Job: {}", job.uri);
+            }
+            printer.sync_spool();
+            Arc::clone(&printer.spool)
+        };
+        spool.flush().await;
+    }
+}
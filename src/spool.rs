@@ -0,0 +1,692 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures::io::{AsyncRead, AsyncReadExt};
+use log::warn;
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt};
+
+use crate::decompress::Decoder;
+use crate::printer::{Compression, Job, JobState, JobStateReason, MimeMediaType};
+
+// Holds the next job-id, so that ids are not reused after the jobs which had them are discarded.
+const NEXT_JOB_ID_FILE: &str = "next-job-id";
+// How much of a document is read at a time while it is written to the spool.
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+// How much compressed data is decoded between checks of the document's size.
+const DECODE_CHUNK_SIZE: usize = 4 * 1024;
+
+// What happens to a job which was being processed when the server stopped.
+#[derive(Copy, Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InterruptedJobs {
+    // The job is aborted, as the output device may already have printed part of it.
+    #[default]
+    Abort,
+    // The job is processed again from the start.
+    Requeue,
+}
+
+// Why a document could not be received.
+#[derive(Debug)]
+pub enum ReceiveError {
+    // The document, once decompressed, is larger than the printer accepts.
+    TooLarge,
+    // The document is not valid for the compression it was sent with.
+    Compression(io::Error),
+    Io(io::Error),
+}
+
+impl From<io::Error> for ReceiveError {
+    fn from(e: io::Error) -> Self {
+        ReceiveError::Io(e)
+    }
+}
+
+// The directory where documents and jobs are kept, in the manner of the CUPS spool directory.
+// Each document is a file named d followed by a serial number, which is independent of the job so
+// that reprocessed jobs can share their documents with the original. Each job is a JSON file named
+// c followed by its job-id. A persistent spool keeps the jobs so that they can be recovered when
+// the server starts again; the temporary spool used without one only holds documents.
+// https://www.cups.org/doc/spec-design.html
+#[derive(Debug)]
+pub struct Spool {
+    dir: PathBuf,
+    persistent: bool,
+    next_document: AtomicU64,
+    staged: Mutex<Staged>,
+    synced: Mutex<Synced>,
+}
+
+// What the spool directory held after the last flush: the record of each job, and the documents
+// each job refers to.
+#[derive(Debug, Default)]
+struct Synced {
+    jobs: HashMap<u32, String>,
+    documents: HashMap<u32, Vec<PathBuf>>,
+    next_job_id: u32,
+}
+
+// Changes to the jobs which have not been flushed yet.
+#[derive(Debug, Default)]
+struct Staged {
+    jobs: HashMap<u32, Option<StagedJob>>,
+    next_job_id: Option<u32>,
+}
+
+impl Staged {
+    fn is_empty(&self) -> bool {
+        self.jobs.is_empty() && self.next_job_id.is_none()
+    }
+}
+
+#[derive(Debug)]
+struct StagedJob {
+    // Only kept for a persistent spool.
+    record: Option<String>,
+    documents: Vec<PathBuf>,
+}
+
+impl Spool {
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        let error = |e: io::Error| format!("{}: {}", dir.display(), e);
+        fs::create_dir_all(dir).map_err(error)?;
+        // Documents are recorded by path, so the spool must not depend on the working directory.
+        let dir = dir.canonicalize().map_err(error)?;
+        let mut last_document = 0;
+        for entry in fs::read_dir(&dir).map_err(error)? {
+            let name = entry.map_err(error)?.file_name();
+            if let Some(serial) = name.to_str().and_then(|name| name.strip_prefix('d')) {
+                last_document = last_document.max(serial.parse().unwrap_or(0));
+            }
+        }
+        Ok(Self {
+            dir,
+            persistent: true,
+            next_document: AtomicU64::new(last_document + 1),
+            staged: Mutex::default(),
+            synced: Mutex::default(),
+        })
+    }
+
+    // A spool for one run of the server, which is created when the first document arrives and
+    // removed when the spool is dropped.
+    pub fn temporary() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        let serial = NEXT.fetch_add(1, Ordering::Relaxed);
+        Self {
+            dir: std::env::temp_dir().join(format!("print-srv-{}-{}", process::id(), serial)),
+            persistent: false,
+            next_document: AtomicU64::new(1),
+            staged: Mutex::default(),
+            synced: Mutex::default(),
+        }
+    }
+
+    // Stores a document as it is read, decompressing it along the way, so that it never has to be
+    // held in memory. Nothing is kept if the document cannot be received.
+    pub async fn receive_document<R>(
+        &self,
+        mut reader: R,
+        compression: Compression,
+        max_size: u64,
+    ) -> Result<(PathBuf, u64), ReceiveError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let path = self.new_document().await?;
+        let mut decoder = Decoder::new(compression);
+        match copy_decoded(&mut reader, &mut decoder, &path, max_size).await {
+            Ok(size) => Ok((path, size)),
+            Err(e) => {
+                self.discard(&path).await;
+                Err(e)
+            }
+        }
+    }
+
+    // Guesses the format of a stored document from its first octets.
+    pub async fn sniff(&self, path: &Path) -> io::Result<MimeMediaType> {
+        let mut head = Vec::with_capacity(MimeMediaType::SNIFF_SIZE);
+        tokio::fs::File::open(path)
+            .await?
+            .take(MimeMediaType::SNIFF_SIZE as u64)
+            .read_to_end(&mut head)
+            .await?;
+        Ok(MimeMediaType::sniff(&head))
+    }
+
+    async fn new_document(&self) -> io::Result<PathBuf> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let serial = self.next_document.fetch_add(1, Ordering::Relaxed);
+        Ok(self.dir.join(format!("d{:08}", serial)))
+    }
+
+    // Removes a document which was written but never became part of a job.
+    pub async fn discard(&self, path: &Path) {
+        if let Err(e) = tokio::fs::remove_file(path).await {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("Could not remove {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    // Records changes to the printer's jobs, to be written by the next flush: each job which has
+    // changed, or None for one which has been discarded. Only these jobs are serialized, so this is
+    // cheap enough to call while the printer is locked.
+    pub fn stage<'a, I>(&self, changes: I, next_job_id: u32) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (u32, Option<&'a Job>)>,
+    {
+        let mut staged = self.staged.lock().unwrap();
+        for (job_id, job) in changes {
+            let change = match job {
+                Some(job) => Some(StagedJob {
+                    record: if self.persistent {
+                        Some(serde_json::to_string_pretty(job)?)
+                    } else {
+                        None
+                    },
+                    documents: job
+                        .documents
+                        .iter()
+                        .map(|document| document.path().to_path_buf())
+                        .collect(),
+                }),
+                None => None,
+            };
+            staged.jobs.insert(job_id, change);
+        }
+        staged.next_job_id = Some(next_job_id);
+        Ok(())
+    }
+
+    // Writes the staged changes on a blocking thread, so that callers need not hold any lock while
+    // the files are written. A failure is only logged; what could not be written is tried again by
+    // the next flush.
+    pub async fn flush(self: &Arc<Self>) {
+        if self.staged.lock().unwrap().is_empty() {
+            return;
+        }
+        let spool = Arc::clone(self);
+        match tokio::task::spawn_blocking(move || spool.write_staged()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Could not update the spool: {}", e),
+            Err(e) => warn!("Could not update the spool: {}", e),
+        }
+    }
+
+    // Brings the spool up to date with the staged changes: jobs which have changed are written, and
+    // the files of jobs which have been discarded are removed. Documents are only removed once no
+    // job refers to them.
+    fn write_staged(&self) -> io::Result<()> {
+        // Holding the synced lock throughout keeps concurrent flushes from writing an older record
+        // of a job over a newer one.
+        let mut synced = self.synced.lock().unwrap();
+        let mut staged = std::mem::take(&mut *self.staged.lock().unwrap());
+        let result = self.write(&mut synced, &mut staged);
+        if result.is_err() {
+            let mut pending = self.staged.lock().unwrap();
+            for (job_id, change) in staged.jobs {
+                pending.jobs.entry(job_id).or_insert(change);
+            }
+            pending.next_job_id = pending.next_job_id.or(staged.next_job_id);
+        }
+        result
+    }
+
+    // Writes the staged changes, taking each out of staged once it has been written.
+    fn write(&self, synced: &mut Synced, staged: &mut Staged) -> io::Result<()> {
+        let job_ids: Vec<u32> = staged.jobs.keys().copied().collect();
+        for job_id in job_ids {
+            let unused = match &staged.jobs[&job_id] {
+                Some(job) => {
+                    if let Some(record) = &job.record {
+                        if synced.jobs.get(&job_id) != Some(record) {
+                            write_atomically(&self.job_path(job_id), record.as_bytes())?;
+                            synced.jobs.insert(job_id, record.clone());
+                        }
+                    }
+                    synced
+                        .documents
+                        .insert(job_id, job.documents.clone())
+                        .unwrap_or_default()
+                }
+                None => {
+                    if synced.jobs.contains_key(&job_id) {
+                        remove(&self.job_path(job_id))?;
+                        synced.jobs.remove(&job_id);
+                    }
+                    synced.documents.remove(&job_id).unwrap_or_default()
+                }
+            };
+            for path in unused {
+                if !synced
+                    .documents
+                    .values()
+                    .flatten()
+                    .any(|used| *used == path)
+                {
+                    remove(&path)?;
+                }
+            }
+            staged.jobs.remove(&job_id);
+        }
+
+        if let Some(next_job_id) = staged.next_job_id {
+            if self.persistent && synced.next_job_id != next_job_id {
+                write_atomically(
+                    &self.dir.join(NEXT_JOB_ID_FILE),
+                    next_job_id.to_string().as_bytes(),
+                )?;
+                synced.next_job_id = next_job_id;
+            }
+            staged.next_job_id = None;
+        }
+        Ok(())
+    }
+
+    // Loads the jobs kept in the spool and the job-id to carry on from. Jobs which were being
+    // processed when the server stopped are aborted or requeued, and jobs whose documents have gone
+    // missing are aborted. Documents which never became part of a job are removed.
+    pub fn recover(&self, interrupted: InterruptedJobs) -> io::Result<(Vec<Job>, u32)> {
+        let mut next_job_id = match fs::read_to_string(self.dir.join(NEXT_JOB_ID_FILE)) {
+            Ok(contents) => contents.trim().parse().unwrap_or(1),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 1,
+            Err(e) => return Err(e),
+        };
+
+        let mut jobs = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if !is_job_record(&path) {
+                continue;
+            }
+            let job = fs::read_to_string(&path)
+                .and_then(|contents| serde_json::from_str::<Job>(&contents).map_err(Into::into));
+            match job {
+                Ok(job) => jobs.push(job),
+                Err(e) => warn!("Ignoring {}: {}", path.display(), e),
+            }
+        }
+        jobs.sort_by_key(|job| job.id);
+
+        let documents: HashSet<&Path> = jobs
+            .iter()
+            .flat_map(|job| &job.documents)
+            .map(|document| document.path())
+            .collect();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|name| name.to_str());
+            let unused = match name {
+                Some(name) if name.ends_with(".tmp") => true,
+                Some(name) if name.starts_with('d') => !documents.contains(path.as_path()),
+                _ => false,
+            };
+            if unused {
+                remove(&path)?;
+            }
+        }
+
+        for job in &mut jobs {
+            let state = job.state();
+            let recovered = if job.documents.iter().any(|d| !d.path().exists()) {
+                if state.is_terminal() {
+                    continue;
+                }
+                warn!("Job {} is missing documents", job.id);
+                job.abort(JobStateReason::AbortedBySystem)
+            } else if matches!(state, JobState::Processing | JobState::ProcessingStopped) {
+                match interrupted {
+                    InterruptedJobs::Abort => job.abort(JobStateReason::AbortedBySystem),
+                    InterruptedJobs::Requeue => {
+                        job.requeue();
+                        Ok(())
+                    }
+                }
+            } else {
+                continue;
+            };
+            if let Err(e) = recovered {
+                warn!("{}", e);
+            }
+        }
+
+        if let Some(last) = jobs.last() {
+            next_job_id = next_job_id.max(last.id + 1);
+        }
+        Ok((jobs, next_job_id))
+    }
+
+    fn job_path(&self, job_id: u32) -> PathBuf {
+        self.dir.join(format!("c{:05}.json", job_id))
+    }
+}
+
+// Decompresses everything the reader has into a new file, giving up once more than max_size octets
+// have come out. Returns the number of octets written.
+async fn copy_decoded<R>(
+    reader: &mut R,
+    decoder: &mut Decoder,
+    path: &Path,
+    max_size: u64,
+) -> Result<u64, ReceiveError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut buf = vec![0u8; COPY_BUFFER_SIZE];
+    let mut size = 0u64;
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        // Compressed data is fed in small pieces so that the size is checked before a highly
+        // compressed document can fill memory.
+        for piece in buf[..n].chunks(DECODE_CHUNK_SIZE) {
+            decoder.write(piece).map_err(ReceiveError::Compression)?;
+            size += write_output(decoder, &mut file, size, max_size).await?;
+        }
+    }
+    decoder.finish().map_err(ReceiveError::Compression)?;
+    size += write_output(decoder, &mut file, size, max_size).await?;
+    file.flush().await?;
+    Ok(size)
+}
+
+async fn write_output(
+    decoder: &mut Decoder,
+    file: &mut tokio::fs::File,
+    size: u64,
+    max_size: u64,
+) -> Result<u64, ReceiveError> {
+    let output = decoder.take_output();
+    let n = output.len() as u64;
+    if size + n > max_size {
+        return Err(ReceiveError::TooLarge);
+    }
+    file.write_all(&output).await?;
+    Ok(n)
+}
+
+fn is_job_record(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('c') && name.ends_with(".json"))
+}
+
+// Writes a file so that it is either entirely replaced or left as it was, even if the server stops
+// part way through.
+impl Drop for Spool {
+    fn drop(&mut self) {
+        if !self.persistent {
+            if let Err(e) = fs::remove_dir_all(&self.dir) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    warn!("Could not remove {}: {}", self.dir.display(), e);
+                }
+            }
+        }
+    }
+}
+
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A job with one document, written to the spool, which has been fully received.
+    fn job(spool: &Spool, id: u32, document: &str) -> Job {
+        let path = spool.dir.join(document);
+        fs::write(&path, b"%PDF-1.4\n").unwrap();
+        let mut job = Job::new(
+            id,
+            format!("ipp://localhost/ipp/print/{}", id),
+            "test",
+            "alice",
+        );
+        job.add_document(MimeMediaType::Pdf, MimeMediaType::Pdf, path, 9, true);
+        job
+    }
+
+    fn processing(mut job: Job) -> Job {
+        job.start().unwrap();
+        job
+    }
+
+    fn completed(job: Job) -> Job {
+        let mut job = processing(job);
+        job.complete().unwrap();
+        job
+    }
+
+    // Writes the jobs to the spool, as a flush would.
+    fn sync(spool: &Spool, jobs: &[Job], next_job_id: u32) {
+        let changes = jobs.iter().map(|job| (job.id, Some(job)));
+        spool.stage(changes, next_job_id).unwrap();
+        spool.write_staged().unwrap();
+    }
+
+    // Removes discarded jobs from the spool, as a flush would.
+    fn remove_jobs(spool: &Spool, job_ids: &[u32], next_job_id: u32) {
+        let changes = job_ids.iter().map(|&job_id| (job_id, None));
+        spool.stage(changes, next_job_id).unwrap();
+        spool.write_staged().unwrap();
+    }
+
+    // Writes the jobs to a spool and reads them back as a restarted server would.
+    fn restart(spool: &Spool, jobs: &[Job], interrupted: InterruptedJobs) -> (Vec<Job>, u32) {
+        let next_job_id = jobs.iter().map(|job| job.id + 1).max().unwrap_or(1);
+        sync(spool, jobs, next_job_id);
+        Spool::open(&spool.dir)
+            .unwrap()
+            .recover(interrupted)
+            .unwrap()
+    }
+
+    fn states(jobs: &[Job]) -> Vec<(u32, JobState)> {
+        jobs.iter().map(|job| (job.id, job.state())).collect()
+    }
+
+    #[test]
+    fn recovers_jobs_and_next_job_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::open(dir.path()).unwrap();
+        let jobs = vec![
+            job(&spool, 1, "d00000001"),
+            completed(job(&spool, 2, "d00000002")),
+        ];
+        sync(&spool, &jobs, 5);
+
+        let (recovered, next_job_id) = Spool::open(dir.path())
+            .unwrap()
+            .recover(InterruptedJobs::Abort)
+            .unwrap();
+        assert_eq!(
+            states(&recovered),
+            vec![(1, JobState::Pending), (2, JobState::Completed)]
+        );
+        assert!(recovered[0].is_ready());
+        assert_eq!(
+            recovered[1].documents[0].path(),
+            jobs[1].documents[0].path()
+        );
+        assert_eq!(next_job_id, 5);
+    }
+
+    #[test]
+    fn next_job_id_follows_last_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::open(dir.path()).unwrap();
+        sync(&spool, &[job(&spool, 7, "d00000001")], 7);
+        let (_, next_job_id) = spool.recover(InterruptedJobs::Abort).unwrap();
+        assert_eq!(next_job_id, 8);
+    }
+
+    #[test]
+    fn aborts_interrupted_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::open(dir.path()).unwrap();
+        let jobs = vec![processing(job(&spool, 1, "d00000001"))];
+        let (recovered, _) = restart(&spool, &jobs, InterruptedJobs::Abort);
+        assert_eq!(states(&recovered), vec![(1, JobState::Aborted)]);
+        assert_eq!(
+            recovered[0].state_reasons(),
+            [JobStateReason::AbortedBySystem]
+        );
+        assert!(recovered[0].documents[0].path().exists());
+    }
+
+    #[test]
+    fn requeues_interrupted_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::open(dir.path()).unwrap();
+        let jobs = vec![
+            processing(job(&spool, 1, "d00000001")),
+            completed(job(&spool, 2, "d00000002")),
+        ];
+        let (recovered, _) = restart(&spool, &jobs, InterruptedJobs::Requeue);
+        assert_eq!(
+            states(&recovered),
+            vec![(1, JobState::Pending), (2, JobState::Completed)]
+        );
+        assert!(recovered[0].is_ready());
+        assert!(recovered[0].time_at_processing().is_none());
+    }
+
+    #[test]
+    fn aborts_jobs_with_missing_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::open(dir.path()).unwrap();
+        let jobs = vec![
+            job(&spool, 1, "d00000001"),
+            processing(job(&spool, 2, "d00000002")),
+            completed(job(&spool, 3, "d00000003")),
+        ];
+        sync(&spool, &jobs, 4);
+        for job in &jobs {
+            fs::remove_file(job.documents[0].path()).unwrap();
+        }
+
+        let (recovered, _) = Spool::open(dir.path())
+            .unwrap()
+            .recover(InterruptedJobs::Requeue)
+            .unwrap();
+        // A job which had already finished is kept as it was, for its attributes.
+        assert_eq!(
+            states(&recovered),
+            vec![
+                (1, JobState::Aborted),
+                (2, JobState::Aborted),
+                (3, JobState::Completed)
+            ]
+        );
+        assert_eq!(
+            recovered[0].state_reasons(),
+            [JobStateReason::AbortedBySystem]
+        );
+    }
+
+    #[test]
+    fn removes_orphan_documents_and_partial_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::open(dir.path()).unwrap();
+        let jobs = vec![job(&spool, 1, "d00000001")];
+        sync(&spool, &jobs, 2);
+        let orphan = spool.dir.join("d00000002");
+        fs::write(&orphan, b"never part of a job").unwrap();
+        let partial = spool.dir.join("c00002.tmp");
+        fs::write(&partial, b"{").unwrap();
+        let unrelated = spool.dir.join("README");
+        fs::write(&unrelated, b"kept").unwrap();
+
+        let (recovered, _) = Spool::open(dir.path())
+            .unwrap()
+            .recover(InterruptedJobs::Abort)
+            .unwrap();
+        assert_eq!(states(&recovered), vec![(1, JobState::Pending)]);
+        assert!(jobs[0].documents[0].path().exists());
+        assert!(!orphan.exists());
+        assert!(!partial.exists());
+        assert!(unrelated.exists());
+    }
+
+    #[test]
+    fn ignores_unreadable_job_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::open(dir.path()).unwrap();
+        sync(&spool, &[job(&spool, 1, "d00000001")], 2);
+        fs::write(spool.dir.join("c00002.json"), b"not json").unwrap();
+
+        let (recovered, next_job_id) = spool.recover(InterruptedJobs::Abort).unwrap();
+        assert_eq!(states(&recovered), vec![(1, JobState::Pending)]);
+        assert_eq!(next_job_id, 2);
+    }
+
+    #[test]
+    fn sync_removes_discarded_jobs_and_unused_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::open(dir.path()).unwrap();
+        let first = completed(job(&spool, 1, "d00000001"));
+        // A reprocessed job shares its documents with the original.
+        let mut copy = Job::new(
+            2,
+            String::from("ipp://localhost/ipp/print/2"),
+            "test",
+            "alice",
+        );
+        let shared = first.documents[0].path().to_path_buf();
+        copy.add_document(
+            MimeMediaType::Pdf,
+            MimeMediaType::Pdf,
+            shared.clone(),
+            9,
+            true,
+        );
+        sync(&spool, &[first, copy], 3);
+        assert!(spool.job_path(1).exists());
+
+        remove_jobs(&spool, &[1], 3);
+        assert!(!spool.job_path(1).exists());
+        assert!(spool.job_path(2).exists());
+        assert!(shared.exists());
+
+        remove_jobs(&spool, &[2], 3);
+        assert!(!spool.job_path(2).exists());
+        assert!(!shared.exists());
+        let (recovered, next_job_id) = spool.recover(InterruptedJobs::Abort).unwrap();
+        assert!(recovered.is_empty());
+        assert_eq!(next_job_id, 3);
+    }
+
+    #[tokio::test]
+    async fn temporary_spool_is_removed_when_dropped() {
+        let spool = Spool::temporary();
+        let (path, _) = spool
+            .receive_document(&b"%PDF-1.4"[..], Compression::None, 100)
+            .await
+            .unwrap();
+        assert!(path.exists());
+        let dir = spool.dir.clone();
+        drop(spool);
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,126 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use log::warn;
+use tokio::net::TcpListener;
+use tokio::time::timeout;
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+use crate::printer::{Printer, UriSecurityMethod};
+
+// How long a client has to complete the TLS handshake after connecting.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+// How long to wait before accepting connections again after accepting one failed, e.g. because
+// the process ran out of file descriptors.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+// Builds the TLS configuration for the ipps listener from a PEM certificate chain and private key.
+// Without them a self-signed certificate is generated for the given host names, which clients
+// will only accept once they have been told to trust it.
+// https://tools.ietf.org/html/rfc7472
+pub fn server_config(
+    cert: Option<&Path>,
+    key: Option<&Path>,
+    hostnames: Vec<String>,
+) -> Result<Arc<ServerConfig>, String> {
+    let (certs, key) = match (cert, key) {
+        (Some(cert), Some(key)) => (load_certs(cert)?, load_key(key)?),
+        (None, None) => self_signed(hostnames)?,
+        _ => return Err(String::from("A certificate and key must be given together")),
+    };
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Invalid certificate or key: {}", e))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<Certificate>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("{}: no PEM certificates found", path.display()));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_key(path: &Path) -> Result<PrivateKey, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let items = rustls_pemfile::read_all(&mut BufReader::new(file))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| format!("{}: no PEM private key found", path.display()))
+}
+
+fn self_signed(hostnames: Vec<String>) -> Result<(Vec<Certificate>, PrivateKey), String> {
+    let cert = rcgen::generate_simple_self_signed(hostnames)
+        .map_err(|e| format!("Could not generate a certificate: {}", e))?;
+    let der = cert
+        .serialize_der()
+        .map_err(|e| format!("Could not generate a certificate: {}", e))?;
+    Ok((
+        vec![Certificate(der)],
+        PrivateKey(cert.serialize_private_key_der()),
+    ))
+}
+
+// Serves IPP over TLS. Requests are handled exactly as they are on the plain listener.
+// https://tools.ietf.org/html/rfc8010#section-4.2
+pub async fn run(
+    mx_printer: Arc<RwLock<Printer>>,
+    addr: SocketAddr,
+    config: Arc<ServerConfig>,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    let acceptor = TlsAcceptor::from(config);
+    println!("Listening for IPPS requests on {}", addr);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Could not accept an IPPS connection: {}", e);
+                tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let mx_printer = Arc::clone(&mx_printer);
+        tokio::spawn(async move {
+            let stream = match timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    warn!("TLS handshake with {} failed: {}", peer, e);
+                    return;
+                }
+                Err(_) => {
+                    warn!("TLS handshake with {} timed out", peer);
+                    return;
+                }
+            };
+            let service = service_fn(move |req| {
+                let mx_printer = Arc::clone(&mx_printer);
+                async move { crate::handle(&mx_printer, UriSecurityMethod::Tls, peer.ip(), req).await }
+            });
+            if let Err(e) = Http::new().serve_connection(stream, service).await {
+                warn!("IPPS connection from {} failed: {}", peer, e);
+            }
+        });
+    }
+}